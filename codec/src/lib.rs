@@ -0,0 +1,483 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The input and journal format shared by the host, the guest and the
+//! `BonsaiStarter` contract.
+
+use std::fmt;
+
+use ethabi::{ParamType, Token};
+use tiny_keccak::{Hasher, Keccak};
+
+/// The error of decoding the guest input or journal.
+#[derive(Debug)]
+pub enum CodecError {
+    /// The data is not valid abi encoding of the expected types.
+    Abi(ethabi::Error),
+    /// The data is not the canonical abi encoding, such as having trailing
+    /// bytes or dirty paddings.
+    NonCanonical,
+    /// The decoded token is not of the expected type.
+    InvalidToken(&'static str),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Abi(err) => write!(f, "invalid abi encoding: {:?}", err),
+            Self::NonCanonical => write!(f, "not the canonical abi encoding"),
+            Self::InvalidToken(name) => write!(f, "invalid token for `{}`", name),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+/// Decode the whole `data`, which should be the canonical encoding of `types`.
+///
+/// `ethabi::decode_whole` only checks the head of the dynamic types, so check
+/// by re-encoding the tokens instead.
+fn decode_strict(types: &[ParamType], data: &[u8]) -> Result<Vec<Token>, CodecError> {
+    let tokens = ethabi::decode(types, data).map_err(CodecError::Abi)?;
+    if ethabi::encode(&tokens) != data {
+        return Err(CodecError::NonCanonical);
+    }
+    Ok(tokens)
+}
+
+fn into_bytes32(token: Token, name: &'static str) -> Result<[u8; 32], CodecError> {
+    token
+        .into_fixed_bytes()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(CodecError::InvalidToken(name))
+}
+
+/// The id of the program registered in `BonsaiStarter`, as
+/// `keccak256(abi.encode(instRoot, funcRoot))`.
+pub fn program_id(inst_root: [u8; 32], func_root: [u8; 32]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    hasher.update(&inst_root);
+    hasher.update(&func_root);
+    let mut id = [0u8; 32];
+    hasher.finalize(&mut id);
+    id
+}
+
+/// The guest input, as `abi.encode(instRoot, funcRoot, proof)` from
+/// `BonsaiStarter.executeOneStep`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OspRequest {
+    pub inst_root: [u8; 32],
+    pub func_root: [u8; 32],
+    /// the SCALE encoded osp proof
+    pub proof: Vec<u8>,
+}
+
+impl OspRequest {
+    const TYPES: [ParamType; 3] = [
+        ParamType::FixedBytes(32),
+        ParamType::FixedBytes(32),
+        ParamType::Bytes,
+    ];
+
+    pub fn encode(&self) -> Vec<u8> {
+        ethabi::encode(&[
+            Token::FixedBytes(self.inst_root.to_vec()),
+            Token::FixedBytes(self.func_root.to_vec()),
+            Token::Bytes(self.proof.clone()),
+        ])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, CodecError> {
+        let mut tokens = decode_strict(&Self::TYPES, data)?.into_iter();
+        let mut next = || tokens.next().ok_or(CodecError::InvalidToken("request"));
+
+        Ok(Self {
+            inst_root: into_bytes32(next()?, "instRoot")?,
+            func_root: into_bytes32(next()?, "funcRoot")?,
+            proof: next()?
+                .into_bytes()
+                .ok_or(CodecError::InvalidToken("proof"))?,
+        })
+    }
+}
+
+/// The input of the multi step guest, as `abi.encode(instRoot, funcRoot,
+/// proofs)`, the osp proofs of the consecutive steps.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OspMultiRequest {
+    pub inst_root: [u8; 32],
+    pub func_root: [u8; 32],
+    /// the SCALE encoded osp proofs
+    pub proofs: Vec<Vec<u8>>,
+}
+
+impl OspMultiRequest {
+    fn types() -> [ParamType; 3] {
+        [
+            ParamType::FixedBytes(32),
+            ParamType::FixedBytes(32),
+            ParamType::Array(Box::new(ParamType::Bytes)),
+        ]
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        ethabi::encode(&[
+            Token::FixedBytes(self.inst_root.to_vec()),
+            Token::FixedBytes(self.func_root.to_vec()),
+            Token::Array(self.proofs.iter().cloned().map(Token::Bytes).collect()),
+        ])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, CodecError> {
+        let mut tokens = decode_strict(&Self::types(), data)?.into_iter();
+        let mut next = || tokens.next().ok_or(CodecError::InvalidToken("request"));
+
+        Ok(Self {
+            inst_root: into_bytes32(next()?, "instRoot")?,
+            func_root: into_bytes32(next()?, "funcRoot")?,
+            proofs: next()?
+                .into_array()
+                .ok_or(CodecError::InvalidToken("proofs"))?
+                .into_iter()
+                .map(|proof| proof.into_bytes().ok_or(CodecError::InvalidToken("proofs")))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// The status of the guest execution, committed in the journal so the contract
+/// can record a rejected proof instead of never getting the callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OspStatus {
+    Ok = 0,
+    /// The input or the osp proof can not be decoded.
+    DecodeError = 1,
+    /// The osp proof failed to run.
+    RunError = 2,
+    /// The inst of the osp proof trapped, so the program ends at the pre
+    /// state.
+    Trapped = 3,
+}
+
+impl TryFrom<u8> for OspStatus {
+    type Error = CodecError;
+
+    fn try_from(status: u8) -> Result<Self, CodecError> {
+        match status {
+            0 => Ok(Self::Ok),
+            1 => Ok(Self::DecodeError),
+            2 => Ok(Self::RunError),
+            3 => Ok(Self::Trapped),
+            _ => Err(CodecError::InvalidToken("status")),
+        }
+    }
+}
+
+/// The guest journal, as the args of
+/// `BonsaiStarter.storeResult(uint8 status, bytes32 instRoot, bytes32
+/// funcRoot, bytes32 preState, bytes32 postState)`.
+///
+/// The code roots bind the state transition to the program. The code roots
+/// and the `pre_state` are zero if the input can not be decoded, and the
+/// `post_state` is zero if the status is not [`OspStatus::Ok`], such as the
+/// trapped step which has no post state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OspJournal {
+    pub status: OspStatus,
+    pub inst_root: [u8; 32],
+    pub func_root: [u8; 32],
+    pub pre_state: [u8; 32],
+    pub post_state: [u8; 32],
+}
+
+impl OspJournal {
+    const TYPES: [ParamType; 5] = [
+        ParamType::Uint(8),
+        ParamType::FixedBytes(32),
+        ParamType::FixedBytes(32),
+        ParamType::FixedBytes(32),
+        ParamType::FixedBytes(32),
+    ];
+
+    pub fn ok(
+        inst_root: [u8; 32],
+        func_root: [u8; 32],
+        pre_state: [u8; 32],
+        post_state: [u8; 32],
+    ) -> Self {
+        Self {
+            status: OspStatus::Ok,
+            inst_root,
+            func_root,
+            pre_state,
+            post_state,
+        }
+    }
+
+    pub fn failed(
+        status: OspStatus,
+        inst_root: [u8; 32],
+        func_root: [u8; 32],
+        pre_state: [u8; 32],
+    ) -> Self {
+        Self {
+            status,
+            inst_root,
+            func_root,
+            pre_state,
+            post_state: [0; 32],
+        }
+    }
+
+    /// The journal of the step whose inst trapped.
+    pub fn trapped(inst_root: [u8; 32], func_root: [u8; 32], pre_state: [u8; 32]) -> Self {
+        Self::failed(OspStatus::Trapped, inst_root, func_root, pre_state)
+    }
+
+    /// The journal of the input which can not be decoded.
+    pub fn undecodable() -> Self {
+        Self::failed(OspStatus::DecodeError, [0; 32], [0; 32], [0; 32])
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        ethabi::encode(&[
+            Token::Uint((self.status as u8).into()),
+            Token::FixedBytes(self.inst_root.to_vec()),
+            Token::FixedBytes(self.func_root.to_vec()),
+            Token::FixedBytes(self.pre_state.to_vec()),
+            Token::FixedBytes(self.post_state.to_vec()),
+        ])
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, CodecError> {
+        let mut tokens = decode_strict(&Self::TYPES, data)?.into_iter();
+        let mut next = || tokens.next().ok_or(CodecError::InvalidToken("journal"));
+
+        let status = next()?
+            .into_uint()
+            .filter(|status| status.bits() <= 8)
+            .ok_or(CodecError::InvalidToken("status"))?;
+
+        Ok(Self {
+            status: OspStatus::try_from(status.low_u32() as u8)?,
+            inst_root: into_bytes32(next()?, "instRoot")?,
+            func_root: into_bytes32(next()?, "funcRoot")?,
+            pre_state: into_bytes32(next()?, "preState")?,
+            post_state: into_bytes32(next()?, "postState")?,
+        })
+    }
+}
+
+/// The journal of the multi step guest, as the args of
+/// `BonsaiStarter.storeStepsResult(uint8 status, bytes32 instRoot, bytes32
+/// funcRoot, bytes32 preState, bytes32 postState, uint64 steps)`.
+///
+/// The `steps` is the number of the osp proofs in the input, so the post
+/// state of the steps is told apart from the post state of one step. It is
+/// zero if the input can not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OspMultiJournal {
+    pub journal: OspJournal,
+    pub steps: u64,
+}
+
+impl OspMultiJournal {
+    const TYPES: [ParamType; 6] = [
+        ParamType::Uint(8),
+        ParamType::FixedBytes(32),
+        ParamType::FixedBytes(32),
+        ParamType::FixedBytes(32),
+        ParamType::FixedBytes(32),
+        ParamType::Uint(64),
+    ];
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = self.journal.encode();
+        data.extend(ethabi::encode(&[Token::Uint(self.steps.into())]));
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, CodecError> {
+        let mut tokens = decode_strict(&Self::TYPES, data)?;
+        let steps = tokens
+            .pop()
+            .and_then(Token::into_uint)
+            .filter(|steps| steps.bits() <= 64)
+            .ok_or(CodecError::InvalidToken("steps"))?;
+
+        Ok(Self {
+            journal: OspJournal::decode(&ethabi::encode(&tokens))?,
+            steps: steps.low_u64(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INST_ROOT: &str = "7080aa6f23c6857049c90bc7103a883b2fbe2f4ab895834a06a87a18d9a60a87";
+    const FUNC_ROOT: &str = "ccc2d8707343c7348538f6d0114fab4e20437ec900592ba0d126fab4e19648fe";
+    const FIB_PROGRAM_ID: &str = "25005a76c4be3c7bec0916620776737fe0e310710f55768a395989d3beca1d5e";
+
+    fn bytes32(hex: &str) -> [u8; 32] {
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        bytes
+    }
+
+    fn request() -> OspRequest {
+        OspRequest {
+            inst_root: bytes32(INST_ROOT),
+            func_root: bytes32(FUNC_ROOT),
+            // not a multiple of 32, so the encoding has padding
+            proof: vec![1, 2, 3],
+        }
+    }
+
+    #[test]
+    fn request_round_trip() {
+        let request = request();
+        assert_eq!(OspRequest::decode(&request.encode()).unwrap(), request);
+
+        let empty = OspRequest {
+            proof: vec![],
+            ..request
+        };
+        assert_eq!(OspRequest::decode(&empty.encode()).unwrap(), empty);
+    }
+
+    #[test]
+    fn multi_request_round_trip() {
+        let request = OspMultiRequest {
+            inst_root: bytes32(INST_ROOT),
+            func_root: bytes32(FUNC_ROOT),
+            proofs: vec![vec![1, 2, 3], vec![], vec![7; 40]],
+        };
+        assert_eq!(OspMultiRequest::decode(&request.encode()).unwrap(), request);
+
+        let empty = OspMultiRequest {
+            proofs: vec![],
+            ..request
+        };
+        assert_eq!(OspMultiRequest::decode(&empty.encode()).unwrap(), empty);
+    }
+
+    #[test]
+    fn journal_round_trip() {
+        let (inst_root, func_root) = (bytes32(INST_ROOT), bytes32(FUNC_ROOT));
+        for journal in [
+            OspJournal::ok(inst_root, func_root, [1; 32], [2; 32]),
+            OspJournal::failed(OspStatus::RunError, inst_root, func_root, [1; 32]),
+            OspJournal::trapped(inst_root, func_root, [1; 32]),
+            OspJournal::undecodable(),
+        ] {
+            assert_eq!(OspJournal::decode(&journal.encode()).unwrap(), journal);
+        }
+    }
+
+    #[test]
+    fn multi_journal_round_trip() {
+        let (inst_root, func_root) = (bytes32(INST_ROOT), bytes32(FUNC_ROOT));
+        for (journal, steps) in [
+            (OspJournal::ok(inst_root, func_root, [1; 32], [2; 32]), 16),
+            (OspJournal::trapped(inst_root, func_root, [1; 32]), 3),
+            (OspJournal::undecodable(), 0),
+            (
+                OspJournal::ok(inst_root, func_root, [1; 32], [2; 32]),
+                u64::MAX,
+            ),
+        ] {
+            let journal = OspMultiJournal { journal, steps };
+            assert_eq!(OspMultiJournal::decode(&journal.encode()).unwrap(), journal);
+        }
+
+        // the journals of one step and of the steps are not mistaken for each
+        // other.
+        let journal = OspJournal::ok(inst_root, func_root, [1; 32], [2; 32]);
+        assert!(OspMultiJournal::decode(&journal.encode()).is_err());
+        let journal = OspMultiJournal { journal, steps: 1 };
+        assert!(OspJournal::decode(&journal.encode()).is_err());
+    }
+
+    #[test]
+    fn reject_trailing_bytes() {
+        let mut data = request().encode();
+        data.extend([0; 32]);
+        assert!(matches!(
+            OspRequest::decode(&data),
+            Err(CodecError::NonCanonical)
+        ));
+
+        let mut data = OspJournal::undecodable().encode();
+        data.push(0);
+        assert!(matches!(
+            OspJournal::decode(&data),
+            Err(CodecError::NonCanonical)
+        ));
+    }
+
+    #[test]
+    fn reject_dirty_padding() {
+        // the proof of 3 bytes is padded with zeros to 32 bytes
+        let mut data = request().encode();
+        *data.last_mut().unwrap() = 1;
+        assert!(matches!(
+            OspRequest::decode(&data),
+            Err(CodecError::NonCanonical)
+        ));
+    }
+
+    #[test]
+    fn reject_invalid_status() {
+        let journal = OspJournal::ok([1; 32], [2; 32], [3; 32], [4; 32]);
+
+        // the status is the last byte of the first word
+        let mut data = journal.encode();
+        data[31] = 4;
+        assert!(matches!(
+            OspJournal::decode(&data),
+            Err(CodecError::InvalidToken("status"))
+        ));
+
+        // the status does not fit in uint8
+        let mut data = journal.encode();
+        data[30] = 1;
+        assert!(matches!(
+            OspJournal::decode(&data),
+            Err(CodecError::InvalidToken("status"))
+        ));
+    }
+
+    #[test]
+    fn program_id_of_code_roots() {
+        let (inst_root, func_root) = (bytes32(INST_ROOT), bytes32(FUNC_ROOT));
+
+        let mut hasher = Keccak::v256();
+        hasher.update(&ethabi::encode(&[
+            Token::FixedBytes(inst_root.to_vec()),
+            Token::FixedBytes(func_root.to_vec()),
+        ]));
+        let mut expected = [0u8; 32];
+        hasher.finalize(&mut expected);
+
+        assert_eq!(program_id(inst_root, func_root), expected);
+        assert_eq!(program_id(inst_root, func_root), bytes32(FIB_PROGRAM_ID));
+        assert_ne!(program_id(func_root, inst_root), expected);
+    }
+}
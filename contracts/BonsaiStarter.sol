@@ -22,48 +22,217 @@ import {BonsaiCallbackReceiver} from "../lib/bonsai-lib-sol/src/BonsaiCallbackRe
 /// @title A starter application using Bonsai through the on-chain relay.
 /// @dev This contract demonstrates one pattern for offloading the computation of an expensive
 //       or difficult to implement function to a RISC Zero guest running on Bonsai.
+//       The WASM programs are registered by their code roots, so one deployment serves them all.
 contract BonsaiStarter is BonsaiCallbackReceiver {
-    mapping(bytes32 => bytes32) public stateCache;
-    
-    /// @notice the inst root for execute
-    bytes32 public instRoot;
+    /// @notice The guest execution succeeded.
+    uint8 public constant STATUS_OK = 0;
 
-    /// @notice the func root for execute
-    bytes32 public funcRoot;
+    /// @notice The guest failed to decode the input or the proof.
+    uint8 public constant STATUS_DECODE_ERROR = 1;
 
-    /// @notice Image ID of the only zkVM binary to accept callbacks from.
+    /// @notice The guest failed to run the proof.
+    uint8 public constant STATUS_RUN_ERROR = 2;
+
+    /// @notice The instruction of the proof trapped, so the program ends at the pre state.
+    uint8 public constant STATUS_TRAPPED = 3;
+
+    /// @notice The code roots of a registered WASM program.
+    struct Program {
+        /// @notice the inst root for execute
+        bytes32 instRoot;
+        /// @notice the func root for execute
+        bytes32 funcRoot;
+    }
+
+    /// @notice The registered programs by program id.
+    mapping(bytes32 => Program) public programs;
+
+    /// @notice The post states by program id and pre state.
+    mapping(bytes32 => mapping(bytes32 => bytes32)) public stateCache;
+
+    /// @notice The status of the last rejected proof by program id and pre state, until a proof of the pre state is
+    ///         accepted. A rejected proof only tells the submitted witness is bad, not the pre state.
+    mapping(bytes32 => mapping(bytes32 => uint8)) public rejectedStates;
+
+    /// @notice The pre states whose instruction traps by program id.
+    mapping(bytes32 => mapping(bytes32 => bool)) public trappedStates;
+
+    /// @notice The post states after the consecutive steps by program id, pre state and number of steps.
+    mapping(bytes32 => mapping(bytes32 => mapping(uint64 => bytes32))) public stepsCache;
+
+    /// @notice The status of the consecutive steps which are rejected or trap at the last step, by program id,
+    ///         pre state and number of steps.
+    mapping(bytes32 => mapping(bytes32 => mapping(uint64 => uint8))) public stepsStatus;
+
+    /// @notice Image ID of the one step zkVM binary to accept callbacks of `executeOneStep` from.
     bytes32 public immutable imageID;
 
+    /// @notice Image ID of the multi step zkVM binary to accept callbacks of `executeSteps` from.
+    bytes32 public immutable multiImageID;
+
     /// @notice Gas limit set on the callback from Bonsai.
     /// @dev Should be set to the maximum amount of gas your callback might reasonably consume.
     uint64 private constant BONSAI_CALLBACK_GAS_LIMIT = 100000;
 
-    /// @notice Initialize the contract, binding it to a specified Bonsai relay and RISC Zero guest image.
-    constructor(IBonsaiRelay bonsaiRelay, bytes32 _imageID, bytes32 _instRoot, bytes32 _funcRoot) BonsaiCallbackReceiver(bonsaiRelay) {
+    /// @notice Initialize the contract, binding it to a specified Bonsai relay and the RISC Zero guest images.
+    constructor(IBonsaiRelay bonsaiRelay, bytes32 _imageID, bytes32 _multiImageID)
+        BonsaiCallbackReceiver(bonsaiRelay)
+    {
         imageID = _imageID;
-        instRoot = _instRoot;
-        funcRoot = _funcRoot;
+        multiImageID = _multiImageID;
+    }
+
+    event ProgramRegistered(bytes32 indexed programId, bytes32 instRoot, bytes32 funcRoot);
+
+    event ExecuteOneStepCallback(
+        bytes32 indexed programId, bytes32 instRoot, bytes32 funcRoot, bytes32 indexed preState, bytes32 postState
+    );
+
+    event ExecuteOneStepTrapped(
+        bytes32 indexed programId, bytes32 instRoot, bytes32 funcRoot, bytes32 indexed preState
+    );
+
+    event ExecuteOneStepRejected(
+        bytes32 indexed programId, bytes32 instRoot, bytes32 funcRoot, bytes32 indexed preState, uint8 status
+    );
+
+    event ExecuteStepsCallback(
+        bytes32 indexed programId,
+        bytes32 instRoot,
+        bytes32 funcRoot,
+        bytes32 indexed preState,
+        uint64 steps,
+        uint8 status,
+        bytes32 postState
+    );
+
+    /// @notice Returns the id of the program with the code roots.
+    function programIdOf(bytes32 instRoot, bytes32 funcRoot) public pure returns (bytes32) {
+        return keccak256(abi.encode(instRoot, funcRoot));
     }
 
-    event ExecuteOneStepCallback(bytes32 instRoot, bytes32 funcRoot, bytes32 indexed preState, bytes32 postState);
+    /// @notice Registers the program with the code roots, returns its id.
+    function registerProgram(bytes32 instRoot, bytes32 funcRoot) external returns (bytes32) {
+        bytes32 programId = programIdOf(instRoot, funcRoot);
+        if (!isRegistered(programId)) {
+            programs[programId] = Program(instRoot, funcRoot);
+            emit ProgramRegistered(programId, instRoot, funcRoot);
+        }
+        return programId;
+    }
 
-    /// @notice Returns the post state after execute one step based on per state.
-    function getPostState(bytes32 preState) external view returns (bytes32) {
-        bytes32 result = stateCache[preState];
+    /// @notice Returns if the program is registered.
+    function isRegistered(bytes32 programId) public view returns (bool) {
+        Program storage program = programs[programId];
+        return program.instRoot != 0 || program.funcRoot != 0;
+    }
+
+    /// @notice Returns the post state after execute one step of the program based on per state.
+    function getPostState(bytes32 programId, bytes32 preState) external view returns (bytes32) {
+        bytes32 result = stateCache[programId][preState];
+        require(result != 0, "value not available in cache");
+        return result;
+    }
+
+    /// @notice Returns the status of the last rejected proof based on pre state, or `STATUS_OK` if not rejected or a
+    ///         later proof of the pre state is accepted.
+    function getRejectedStatus(bytes32 programId, bytes32 preState) external view returns (uint8) {
+        return rejectedStates[programId][preState];
+    }
+
+    /// @notice Returns if the program traps at the pre state, so it has no post state.
+    function isTrapped(bytes32 programId, bytes32 preState) external view returns (bool) {
+        return trappedStates[programId][preState];
+    }
+
+    /// @notice Returns the post state after the consecutive steps of the program from the pre state.
+    function getStepsPostState(bytes32 programId, bytes32 preState, uint64 steps) external view returns (bytes32) {
+        bytes32 result = stepsCache[programId][preState][steps];
         require(result != 0, "value not available in cache");
         return result;
     }
 
-    /// @notice Callback function logic for processing verified journals from Bonsai.
-    function storeResult(bytes32 preState, bytes32 postState) external onlyBonsaiCallback(imageID) {
-        emit ExecuteOneStepCallback(instRoot, funcRoot, preState, postState);
-        stateCache[preState] = postState;
+    /// @notice Callback function logic for processing verified journals of one step from Bonsai.
+    /// @dev The journal commits the code roots, which should be of a registered program unless the input is
+    ///      not decodable.
+    function storeResult(uint8 status, bytes32 instRoot, bytes32 funcRoot, bytes32 preState, bytes32 postState)
+        external
+        onlyBonsaiCallback(imageID)
+    {
+        bytes32 programId = programIdOf(instRoot, funcRoot);
+        if (status != STATUS_DECODE_ERROR) {
+            require(isRegistered(programId), "unexpected code roots");
+        }
+
+        if (status == STATUS_TRAPPED) {
+            emit ExecuteOneStepTrapped(programId, instRoot, funcRoot, preState);
+            trappedStates[programId][preState] = true;
+            delete rejectedStates[programId][preState];
+            return;
+        }
+
+        if (status != STATUS_OK) {
+            emit ExecuteOneStepRejected(programId, instRoot, funcRoot, preState, status);
+            if (stateCache[programId][preState] == 0 && !trappedStates[programId][preState]) {
+                rejectedStates[programId][preState] = status;
+            }
+            return;
+        }
+
+        emit ExecuteOneStepCallback(programId, instRoot, funcRoot, preState, postState);
+        stateCache[programId][preState] = postState;
+        delete rejectedStates[programId][preState];
+    }
+
+    /// @notice Callback function logic for processing verified journals of consecutive steps from Bonsai.
+    /// @dev The post state is `steps` steps after the pre state, so it is cached apart from the one step post
+    ///      states. A rejection never overrides the accepted post state of the same steps.
+    function storeStepsResult(
+        uint8 status,
+        bytes32 instRoot,
+        bytes32 funcRoot,
+        bytes32 preState,
+        bytes32 postState,
+        uint64 steps
+    ) external onlyBonsaiCallback(multiImageID) {
+        bytes32 programId = programIdOf(instRoot, funcRoot);
+        if (status != STATUS_DECODE_ERROR) {
+            require(isRegistered(programId), "unexpected code roots");
+        }
+
+        emit ExecuteStepsCallback(programId, instRoot, funcRoot, preState, steps, status, postState);
+        if (status == STATUS_OK) {
+            stepsCache[programId][preState][steps] = postState;
+            delete stepsStatus[programId][preState][steps];
+        } else if (stepsCache[programId][preState][steps] == 0) {
+            stepsStatus[programId][preState][steps] = status;
+        }
+    }
+
+    /// @notice Sends a request to Bonsai to have have the executeOneStep of the program return
+    function executeOneStep(bytes32 programId, bytes calldata proof) external {
+        require(isRegistered(programId), "program not registered");
+        Program storage program = programs[programId];
+        bonsaiRelay.requestCallback(
+            imageID,
+            abi.encode(program.instRoot, program.funcRoot, proof),
+            address(this),
+            this.storeResult.selector,
+            BONSAI_CALLBACK_GAS_LIMIT
+        );
     }
 
-    /// @notice Sends a request to Bonsai to have have the executeOneStep return
-    function executeOneStep(bytes calldata proof) external {
+    /// @notice Sends a request to Bonsai to execute the consecutive steps of the program in one guest run
+    function executeSteps(bytes32 programId, bytes[] calldata proofs) external {
+        require(isRegistered(programId), "program not registered");
+        require(proofs.length > 0, "no step to execute");
+        Program storage program = programs[programId];
         bonsaiRelay.requestCallback(
-            imageID, abi.encode(instRoot, funcRoot, proof), address(this), this.storeResult.selector, BONSAI_CALLBACK_GAS_LIMIT
+            multiImageID,
+            abi.encode(program.instRoot, program.funcRoot, proofs),
+            address(this),
+            this.storeStepsResult.selector,
+            BONSAI_CALLBACK_GAS_LIMIT
         );
     }
 }
@@ -0,0 +1,142 @@
+use std::{io::Write, str::FromStr};
+
+use anyhow::{ensure, Context, Error, Result};
+use serde::Serialize;
+
+use crate::{imports::HostImports, osp::*};
+
+/// The steps to generate osp proofs for, as a range `100..200`, an inclusive
+/// range `100..=200` or a list `5,17,99`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Steps(pub Vec<u64>);
+
+impl FromStr for Steps {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |step: &str| {
+            step.trim()
+                .parse::<u64>()
+                .with_context(|| format!("invalid step `{}`", step))
+        };
+
+        let steps = if let Some((start, end)) = s.split_once("..=") {
+            (parse(start)?..=parse(end)?).collect::<Vec<_>>()
+        } else if let Some((start, end)) = s.split_once("..") {
+            (parse(start)?..parse(end)?).collect::<Vec<_>>()
+        } else {
+            s.split(',').map(parse).collect::<Result<Vec<_>>>()?
+        };
+
+        ensure!(!steps.is_empty(), "no step in `{}`", s);
+        Ok(Self(steps))
+    }
+}
+
+/// The osp proof of one step, with the state hashes before and after it.
+#[derive(Debug, Clone, Serialize)]
+pub struct StepProof {
+    pub step: u64,
+    pub pre_state: String,
+    pub post_state: String,
+    /// the trap of the inst, with the post state zero
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trap: Option<String>,
+    pub proof: String,
+}
+
+impl StepProof {
+    fn new(step: u64, input: OspInput) -> Self {
+        Self {
+            step,
+            pre_state: format!("0x{}", hex::encode(input.pre_state)),
+            post_state: format!("0x{}", hex::encode(input.post_state)),
+            trap: input.trap,
+            proof: format!("0x{}", hex::encode(input.proof)),
+        }
+    }
+}
+
+/// Generate the osp proofs of all the steps, and write them to `out` as json
+/// lines. The ascending steps are generated by one execution, which restarts
+/// only for a step behind the previous one.
+pub fn gen_step_proofs(
+    program: &Program<impl HostImports<()>>,
+    steps: &Steps,
+    mut out: impl Write,
+) -> Result<()> {
+    let generator = OspGenerator::new(program)?;
+    let mut execution = generator.execution()?;
+
+    for &step in &steps.0 {
+        let input = execution
+            .osp_input(step)
+            .with_context(|| format!("gen osp proof for step {}", step))?;
+
+        serde_json::to_writer(&mut out, &StepProof::new(step, input))?;
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{call::Call, imports::StubImports};
+
+    fn steps(s: &str) -> Vec<u64> {
+        s.parse::<Steps>().unwrap().0
+    }
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(steps("100..200"), (100..200).collect::<Vec<_>>());
+        assert_eq!(steps("100..=200"), (100..=200).collect::<Vec<_>>());
+        assert_eq!(steps("5..=5"), vec![5]);
+        assert_eq!(steps(" 1 .. 3 "), vec![1, 2]);
+    }
+
+    #[test]
+    fn parse_lists() {
+        assert_eq!(steps("5,17,99"), vec![5, 17, 99]);
+        assert_eq!(steps("5, 17"), vec![5, 17]);
+        assert_eq!(steps("42"), vec![42]);
+    }
+
+    #[test]
+    fn reject_invalid_steps() {
+        for s in ["5..5", "6..5", "6..=5", "", "5,", "a..b", "-1", "1..=x"] {
+            assert!(s.parse::<Steps>().is_err(), "`{}` should be rejected", s);
+        }
+    }
+
+    #[test]
+    fn gen_proofs_of_osp_inputs() {
+        let program = Program {
+            source: ModuleSource::Builtin,
+            imports: StubImports::default(),
+            call: Call::fib(),
+            run_start: false,
+        };
+        let generator = OspGenerator::new(&program).unwrap();
+        let total = generator.finish().unwrap().steps;
+
+        // the descending steps restart the execution for each step.
+        let ascending = (0..total).collect::<Vec<_>>();
+        let descending = ascending.iter().rev().copied().collect::<Vec<_>>();
+        for steps in [ascending, descending] {
+            let mut out = Vec::new();
+            gen_step_proofs(&program, &Steps(steps.clone()), &mut out).unwrap();
+
+            let lines = String::from_utf8(out).unwrap();
+            let lines = lines.lines().collect::<Vec<_>>();
+            assert_eq!(lines.len(), steps.len());
+            for (line, &step) in lines.iter().zip(&steps) {
+                let input = generator.gen_osp_input(step).unwrap();
+                let expected = serde_json::to_string(&StepProof::new(step, input)).unwrap();
+                assert_eq!(*line, expected, "proof of step {}", step);
+            }
+        }
+    }
+}
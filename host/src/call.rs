@@ -0,0 +1,123 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Error, Result};
+use serde::Deserialize;
+use wasmi::core::{Value, ValueType, F32, F64};
+
+/// A typed argument for the called function, parsed from `<type>:<value>`,
+/// such as `i32:10` or `f64:0.5`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum CallArg {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+
+impl CallArg {
+    pub fn value_type(&self) -> ValueType {
+        match self {
+            Self::I32(_) => ValueType::I32,
+            Self::I64(_) => ValueType::I64,
+            Self::F32(_) => ValueType::F32,
+            Self::F64(_) => ValueType::F64,
+        }
+    }
+}
+
+impl FromStr for CallArg {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (ty, value) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("arg `{}` should be `<type>:<value>`", s))?;
+
+        let arg = match ty {
+            "i32" => Self::I32(value.parse()?),
+            "i64" => Self::I64(value.parse()?),
+            "f32" => Self::F32(value.parse()?),
+            "f64" => Self::F64(value.parse()?),
+            _ => bail!("unknown arg type `{}`, expect one of i32/i64/f32/f64", ty),
+        };
+
+        Ok(arg)
+    }
+}
+
+impl TryFrom<String> for CallArg {
+    type Error = Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<CallArg> for Value {
+    fn from(arg: CallArg) -> Self {
+        match arg {
+            CallArg::I32(v) => Value::I32(v),
+            CallArg::I64(v) => Value::I64(v),
+            CallArg::F32(v) => Value::F32(F32::from(v)),
+            CallArg::F64(v) => Value::F64(F64::from(v)),
+        }
+    }
+}
+
+/// The exported function to execute and the args to call it with.
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub func: String,
+    pub args: Vec<CallArg>,
+}
+
+impl Call {
+    /// Call `fib(10)` in the built-in fib module.
+    pub fn fib() -> Self {
+        Self {
+            func: "fib".to_string(),
+            args: vec![CallArg::I32(10)],
+        }
+    }
+
+    pub fn inputs(&self) -> Vec<Value> {
+        self.args.iter().copied().map(Value::from).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_typed_args() {
+        assert_eq!("i32:10".parse::<CallArg>().unwrap(), CallArg::I32(10));
+        assert_eq!("i64:-1".parse::<CallArg>().unwrap(), CallArg::I64(-1));
+        assert_eq!("f32:1.5".parse::<CallArg>().unwrap(), CallArg::F32(1.5));
+        assert_eq!("f64:-0.5".parse::<CallArg>().unwrap(), CallArg::F64(-0.5));
+        assert_eq!(
+            "i64:9223372036854775807".parse::<CallArg>().unwrap(),
+            CallArg::I64(i64::MAX)
+        );
+    }
+
+    #[test]
+    fn reject_invalid_args() {
+        for arg in [
+            "10",
+            "i8:10",
+            "I32:10",
+            "i32:",
+            "i32:1.5",
+            "i32:2147483648",
+            "f64:abc",
+        ] {
+            assert!(
+                arg.parse::<CallArg>().is_err(),
+                "`{}` should be rejected",
+                arg
+            );
+        }
+    }
+}
@@ -0,0 +1,372 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{imports::HostImports, osp::*};
+
+pub type StateHash = [u8; 32];
+
+/// Parse a `0x` prefixed state hash.
+pub fn parse_hash(s: &str) -> Result<StateHash> {
+    let bytes = hex::decode(s.trim().trim_start_matches("0x"))
+        .with_context(|| format!("invalid hash `{}`", s))?;
+    bytes
+        .try_into()
+        .map_err(|_| anyhow!("hash `{}` should be 32 bytes", s))
+}
+
+fn fmt_hash(hash: &StateHash) -> String {
+    format!("0x{}", hex::encode(hash))
+}
+
+/// The counterparty of the dispute, which claims the state hashes during the
+/// bisection.
+pub trait Counterparty {
+    /// The state hash claimed by the counterparty before executing `step`.
+    fn state_at(&mut self, step: u64) -> Result<StateHash>;
+
+    /// The state hash already claimed at `step` if any, without asking the
+    /// counterparty.
+    fn claimed_at(&self, _step: u64) -> Option<StateHash> {
+        None
+    }
+}
+
+/// Ask the counterparty state hashes on the terminal.
+pub struct Prompt;
+
+impl Counterparty for Prompt {
+    fn state_at(&mut self, step: u64) -> Result<StateHash> {
+        eprint!("counterparty state hash at step {}: ", step);
+        io::stderr().flush()?;
+
+        let mut line = String::new();
+        io::stdin().lock().read_line(&mut line)?;
+        parse_hash(&line)
+    }
+}
+
+#[derive(Deserialize)]
+struct ClaimedStep {
+    step: u64,
+    pre_state: String,
+}
+
+/// The state hashes claimed by the counterparty, loaded from the json lines
+/// generated by `host --steps`.
+pub struct ClaimedSteps(BTreeMap<u64, StateHash>);
+
+impl ClaimedSteps {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let lines = fs::read_to_string(path)
+            .with_context(|| format!("read claimed steps {}", path.display()))?;
+
+        let mut claims = BTreeMap::new();
+        for line in lines.lines().filter(|line| !line.trim().is_empty()) {
+            let claim: ClaimedStep = serde_json::from_str(line)
+                .with_context(|| format!("parse claimed step `{}`", line))?;
+            claims.insert(claim.step, parse_hash(&claim.pre_state)?);
+        }
+
+        Ok(Self(claims))
+    }
+}
+
+impl Counterparty for ClaimedSteps {
+    fn state_at(&mut self, step: u64) -> Result<StateHash> {
+        self.0
+            .get(&step)
+            .copied()
+            .ok_or_else(|| anyhow!("counterparty has no state hash claimed at step {}", step))
+    }
+
+    fn claimed_at(&self, step: u64) -> Option<StateHash> {
+        self.0.get(&step).copied()
+    }
+}
+
+/// Our state before executing `step`.
+fn state_at<I: HostImports<()>>(
+    execution: &mut Execution<'_, '_, I>,
+    step: u64,
+) -> Result<StateHash> {
+    let (osp_proof, _) = execution.osp_proof(step)?;
+    Ok(osp_proof.hash())
+}
+
+/// The first diverging step found by the bisection, with the osp proof to
+/// settle it on chain.
+#[derive(Debug, Clone, Serialize)]
+pub struct Divergence {
+    /// the step both sides agree the state before, but not after
+    pub step: u64,
+    pub pre_state: String,
+    pub post_state: String,
+    pub counterparty_post_state: String,
+    /// the guest input `abi.encode(instRoot, funcRoot, proof)`
+    pub input: String,
+    /// the calldata of `BonsaiStarter.executeOneStep(programId, proof)`
+    pub calldata: String,
+}
+
+/// Bisect the steps in `0..=end` to find the first step where our state and
+/// the counterparty's diverge, both sides should agree on the state at step 0
+/// and claim different states at step `end`.
+///
+/// The state at `end` is the final state after the last step if the program
+/// finishes by `end`, and `end` is then the total steps of the program. The
+/// counterparty's state at `end` is `theirs`, and should agree with its
+/// claims if any.
+///
+/// A divergence from the return of the start function to the entry of the
+/// call is rejected, as no osp proof bridges the two states.
+pub fn bisect<I: HostImports<()>>(
+    program: &Program<I>,
+    counterparty: &mut impl Counterparty,
+    end: u64,
+    ours: Option<StateHash>,
+    theirs: StateHash,
+) -> Result<Divergence> {
+    ensure!(
+        end > 0,
+        "no dispute at step 0, both sides should agree on the initial state"
+    );
+
+    let generator = OspGenerator::new(program)?;
+    let mut execution = generator.execution()?;
+    let (end, our_end) = match execution.seek(end)? {
+        Ok(_) => (end, state_at(&mut execution, end)?),
+        Err(finished) => {
+            let last = finished
+                .steps
+                .checked_sub(1)
+                .ok_or_else(|| anyhow!("program finished without any step"))?;
+            if end > finished.steps {
+                log::warn!(
+                    "{}, dispute the final state at step {}",
+                    finished,
+                    finished.steps
+                );
+            }
+            (finished.steps, execution.osp_input(last)?.post_state)
+        }
+    };
+    if let Some(ours) = ours {
+        ensure!(
+            ours == our_end,
+            "our claimed state {} is not the executed state {} at step {}",
+            fmt_hash(&ours),
+            fmt_hash(&our_end),
+            end
+        );
+    }
+    if let Some(claimed) = counterparty.claimed_at(end) {
+        ensure!(
+            claimed == theirs,
+            "the counterparty claims {} at step {}, not {}",
+            fmt_hash(&claimed),
+            end,
+            fmt_hash(&theirs)
+        );
+    }
+    if our_end == theirs {
+        bail!(
+            "no dispute, both sides claim {} at step {}",
+            fmt_hash(&theirs),
+            end
+        );
+    }
+
+    let (our_start, their_start) = (state_at(&mut execution, 0)?, counterparty.state_at(0)?);
+    ensure!(
+        our_start == their_start,
+        "the initial states diverge: {} vs {}",
+        fmt_hash(&our_start),
+        fmt_hash(&their_start)
+    );
+
+    // the states agree at `lo` and diverge at `hi`.
+    let (mut lo, mut hi, mut their_hi) = (0, end, theirs);
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        let (our_mid, their_mid) = (state_at(&mut execution, mid)?, counterparty.state_at(mid)?);
+        log::info!(
+            "bisect [{}, {}] at {}: {} vs {}",
+            lo,
+            hi,
+            mid,
+            fmt_hash(&our_mid),
+            fmt_hash(&their_mid)
+        );
+
+        if our_mid == their_mid {
+            lo = mid;
+        } else {
+            hi = mid;
+            their_hi = their_mid;
+        }
+    }
+
+    let input = execution.osp_input(lo)?;
+    ensure!(
+        !execution.returns_from_start(lo)?,
+        "the states diverge from the start function to the call after step {}, which no osp \
+         proof can settle",
+        lo
+    );
+
+    log::info!(
+        "diverge at step {}: {} -> {}, counterparty claims {}",
+        lo,
+        fmt_hash(&input.pre_state),
+        fmt_hash(&input.post_state),
+        fmt_hash(&their_hi)
+    );
+
+    Ok(Divergence {
+        step: lo,
+        pre_state: fmt_hash(&input.pre_state),
+        post_state: fmt_hash(&input.post_state),
+        counterparty_post_state: fmt_hash(&their_hi),
+        input: format!("0x{}", hex::encode(input.input())),
+        calldata: format!("0x{}", hex::encode(input.calldata())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{call::Call, imports::StubImports};
+
+    fn fib() -> Program {
+        Program {
+            source: ModuleSource::Builtin,
+            imports: StubImports::default(),
+            call: Call::fib(),
+            run_start: false,
+        }
+    }
+
+    /// Our states before each step, and the final state after the last step.
+    fn states(program: &Program) -> Vec<StateHash> {
+        let generator = OspGenerator::new(program).unwrap();
+        let mut execution = generator.execution().unwrap();
+
+        let mut states = Vec::new();
+        for step in 0.. {
+            match execution.step_proof(step).unwrap() {
+                Ok((_, osp_proof)) => states.push(osp_proof.hash()),
+                Err(finished) => {
+                    let last = execution.osp_input(finished.steps - 1).unwrap();
+                    states.push(last.post_state);
+                    return states;
+                }
+            }
+        }
+        unreachable!()
+    }
+
+    /// The counterparty which claims our states before step `wrong`, and
+    /// wrong states from it.
+    struct Diverged {
+        states: Vec<StateHash>,
+        wrong: u64,
+    }
+
+    impl Counterparty for Diverged {
+        fn state_at(&mut self, step: u64) -> Result<StateHash> {
+            let state = self.states[step as usize];
+            Ok(if step >= self.wrong {
+                state.map(|byte| !byte)
+            } else {
+                state
+            })
+        }
+    }
+
+    fn dispute(end: u64, wrong: u64) -> Divergence {
+        let program = fib();
+        let states = states(&program);
+        let mut counterparty = Diverged { states, wrong };
+        let theirs = counterparty.state_at(end).unwrap();
+
+        let divergence = bisect(&program, &mut counterparty, end, None, theirs).unwrap();
+        assert_eq!(
+            divergence.post_state,
+            fmt_hash(&counterparty.states[divergence.step as usize + 1])
+        );
+        assert_ne!(divergence.post_state, divergence.counterparty_post_state);
+        divergence
+    }
+
+    #[test]
+    fn diverge_at_first_step() {
+        let length = states(&fib()).len() as u64 - 1;
+        assert_eq!(dispute(length / 2, 1).step, 0);
+    }
+
+    #[test]
+    fn diverge_at_last_step() {
+        let end = states(&fib()).len() as u64 / 2;
+        assert_eq!(dispute(end, end).step, end - 1);
+    }
+
+    #[test]
+    fn diverge_at_final_state() {
+        let length = states(&fib()).len() as u64 - 1;
+        assert_eq!(dispute(length, length).step, length - 1);
+        assert_eq!(dispute(length, length / 3).step, length / 3 - 1);
+    }
+
+    #[test]
+    fn no_dispute_on_same_state() {
+        let program = fib();
+        let states = states(&program);
+        let end = states.len() as u64 - 1;
+        let theirs = states[end as usize];
+
+        let mut counterparty = Diverged {
+            states,
+            wrong: u64::MAX,
+        };
+        assert!(bisect(&program, &mut counterparty, end, None, theirs).is_err());
+    }
+
+    #[test]
+    fn reject_dispute_at_initial_state() {
+        let program = fib();
+        let states = states(&program);
+        let theirs = states[0].map(|byte| !byte);
+
+        let mut counterparty = Diverged { states, wrong: 0 };
+        assert!(bisect(&program, &mut counterparty, 0, None, theirs).is_err());
+    }
+
+    #[test]
+    fn check_theirs_against_claims() {
+        let program = fib();
+        let mut counterparty = Diverged {
+            states: states(&program),
+            wrong: 10,
+        };
+        let end = 20;
+        let claims = (0..=end)
+            .map(|step| (step, counterparty.state_at(step).unwrap()))
+            .collect::<BTreeMap<_, _>>();
+
+        // `--theirs` disagrees with the claims at `end`.
+        let mut claims = ClaimedSteps(claims);
+        let err = bisect(&program, &mut claims, end, None, [7; 32]).unwrap_err();
+        assert!(err.to_string().contains("counterparty claims"), "{}", err);
+
+        let theirs = counterparty.state_at(end).unwrap();
+        let divergence = bisect(&program, &mut claims, end, None, theirs).unwrap();
+        assert_eq!(divergence.step, 9);
+    }
+}
@@ -0,0 +1,121 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use bonsai_starter_codec::OspMultiJournal;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::osp::{OspInput, OspMultiInput};
+
+/// The format to emit the guest input or the calldata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EmitFormat {
+    /// `0x` prefixed hex string
+    Hex,
+    /// json object with the data and the decoded fields
+    Json,
+    /// raw bytes
+    Binary,
+}
+
+/// The decoded fields of the emitted data, of one step or of the consecutive
+/// steps of `--multi`.
+#[derive(Serialize)]
+pub struct Emitted {
+    program_id: String,
+    inst_root: String,
+    func_root: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proof: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    proofs: Option<Vec<String>>,
+    /// the number of the steps of `--multi`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    steps: Option<u64>,
+    pre_state: String,
+    post_state: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trap: Option<String>,
+}
+
+#[derive(Serialize)]
+struct WithData<'a> {
+    #[serde(flatten)]
+    emitted: &'a Emitted,
+    data: String,
+}
+
+fn to_hex(bytes: impl AsRef<[u8]>) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+impl Emitted {
+    /// The fields of the one step `input`.
+    pub fn step(input: &OspInput) -> Self {
+        Self {
+            program_id: to_hex(input.program_id()),
+            inst_root: to_hex(input.code_proof.inst_root),
+            func_root: to_hex(input.code_proof.func_root),
+            proof: Some(to_hex(&input.proof)),
+            proofs: None,
+            steps: None,
+            pre_state: to_hex(input.pre_state),
+            post_state: to_hex(input.post_state),
+            trap: input.trap.clone(),
+        }
+    }
+
+    /// The fields of the multi step `input`, from the pre state of the first
+    /// step to the post state of the last step.
+    pub fn steps(input: &OspMultiInput) -> Self {
+        let OspMultiJournal { journal, steps } = input.journal();
+        let last = &input.inputs[input.inputs.len() - 1];
+        Self {
+            program_id: to_hex(input.program_id()),
+            inst_root: to_hex(journal.inst_root),
+            func_root: to_hex(journal.func_root),
+            proof: None,
+            proofs: Some(
+                input
+                    .inputs
+                    .iter()
+                    .map(|input| to_hex(&input.proof))
+                    .collect(),
+            ),
+            steps: Some(steps),
+            pre_state: to_hex(journal.pre_state),
+            post_state: to_hex(journal.post_state),
+            trap: last.trap.clone(),
+        }
+    }
+}
+
+/// Write the `data` with the `emitted` fields to `path` in `format`, or to
+/// stdout if `path` is `-`.
+pub fn emit(path: &Path, format: EmitFormat, emitted: &Emitted, data: &[u8]) -> Result<()> {
+    let bytes = match format {
+        EmitFormat::Hex => to_hex(data).into_bytes(),
+        EmitFormat::Json => serde_json::to_vec_pretty(&WithData {
+            emitted,
+            data: to_hex(data),
+        })?,
+        EmitFormat::Binary => data.to_vec(),
+    };
+
+    if path == Path::new("-") {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(&bytes)?;
+        if format != EmitFormat::Binary {
+            writeln!(stdout)?;
+        }
+        stdout.flush().context("Failed to flush stdout buffer")?;
+    } else {
+        fs::write(path, bytes).with_context(|| format!("write {}", path.display()))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use serde::Deserialize;
+use wasmi::{core::Value, ExternType, Func, Linker, Module, Store};
+
+use crate::call::CallArg;
+
+/// The host functions to define in the linker for the module imports.
+///
+/// Implement this for custom host functions, or use [`StubImports`] for the
+/// imports which just return constants.
+pub trait HostImports<T> {
+    fn define(&self, store: &mut Store<T>, linker: &mut Linker<T>, module: &Module) -> Result<()>;
+}
+
+/// A stub host function which returns constant results.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StubImport {
+    pub module: String,
+    pub name: String,
+    /// the results to return, as `<type>:<value>` such as `i32:0`
+    #[serde(default)]
+    pub results: Vec<CallArg>,
+}
+
+/// The manifest of the stub host functions, such as:
+///
+/// ```json
+/// { "imports": [{ "module": "env", "name": "abort" }] }
+/// ```
+///
+/// The params of the stubs are taken from the module imports.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StubImports {
+    pub imports: Vec<StubImport>,
+}
+
+impl StubImports {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let manifest =
+            fs::read(path).with_context(|| format!("read imports {}", path.display()))?;
+        serde_json::from_slice(&manifest)
+            .with_context(|| format!("parse imports {}", path.display()))
+    }
+}
+
+impl<T> HostImports<T> for StubImports {
+    fn define(&self, store: &mut Store<T>, linker: &mut Linker<T>, module: &Module) -> Result<()> {
+        for stub in &self.imports {
+            let import = module
+                .imports()
+                .find(|import| import.module() == stub.module && import.name() == stub.name)
+                .ok_or_else(|| anyhow!("module does not import `{}.{}`", stub.module, stub.name))?;
+
+            let func_type = match import.ty() {
+                ExternType::Func(func_type) => func_type.clone(),
+                ty => {
+                    return Err(anyhow!(
+                        "import `{}.{}` is not a function: {:?}",
+                        stub.module,
+                        stub.name,
+                        ty
+                    ))
+                }
+            };
+
+            let results = stub
+                .results
+                .iter()
+                .map(|arg| arg.value_type())
+                .collect::<Vec<_>>();
+            ensure!(
+                results == func_type.results(),
+                "import `{}.{}` expects results {:?}, got {:?}",
+                stub.module,
+                stub.name,
+                func_type.results(),
+                results
+            );
+
+            let values = stub
+                .results
+                .iter()
+                .copied()
+                .map(Value::from)
+                .collect::<Vec<_>>();
+            let func = Func::new(&mut *store, func_type, move |_caller, _params, results| {
+                results.copy_from_slice(&values);
+                Ok(())
+            });
+
+            linker
+                .define(&stub.module, &stub.name, func)
+                .map_err(|err| anyhow!("define import `{}.{}`: {}", stub.module, stub.name, err))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wat::parse_str;
+
+    use super::*;
+    use crate::{
+        call::Call,
+        osp::{Ending, ModuleSource, OspGenerator, Program},
+    };
+
+    const MODULE: &str = r#"(module
+        (import "env" "abort" (func $abort (param i32)))
+        (import "env" "now" (func $now (result i64)))
+        (func (export "main") (result i64)
+            i32.const 0
+            call $abort
+            call $now))"#;
+
+    fn stub(name: &str, results: Vec<CallArg>) -> StubImport {
+        StubImport {
+            module: "env".to_string(),
+            name: name.to_string(),
+            results,
+        }
+    }
+
+    fn program(wat: &str, imports: Vec<StubImport>) -> Program {
+        Program {
+            source: ModuleSource::Wasm(parse_str(wat).unwrap()),
+            imports: StubImports { imports },
+            call: Call {
+                func: "main".to_string(),
+                args: vec![],
+            },
+            run_start: false,
+        }
+    }
+
+    fn define_error(wat: &str, imports: Vec<StubImport>) -> String {
+        let program = program(wat, imports);
+        let generator = OspGenerator::new(&program).unwrap();
+        match generator.execution() {
+            Ok(_) => panic!("the imports should be rejected"),
+            Err(err) => format!("{:#}", err),
+        }
+    }
+
+    #[test]
+    fn reject_invalid_stubs() {
+        let error = define_error(
+            MODULE,
+            vec![stub("abort", vec![]), stub("now", vec![CallArg::I32(0)])],
+        );
+        assert!(error.contains("expects results"), "{}", error);
+
+        let error = define_error(MODULE, vec![stub("abort", vec![CallArg::I32(0)])]);
+        assert!(error.contains("expects results"), "{}", error);
+
+        let error = define_error(
+            r#"(module (import "env" "memory" (memory 1)))"#,
+            vec![stub("memory", vec![])],
+        );
+        assert!(error.contains("is not a function"), "{}", error);
+
+        let error = define_error(MODULE, vec![stub("exit", vec![])]);
+        assert!(error.contains("does not import"), "{}", error);
+    }
+
+    #[test]
+    fn prove_steps_calling_stubs() {
+        let program = program(
+            MODULE,
+            vec![stub("abort", vec![]), stub("now", vec![CallArg::I64(42)])],
+        );
+        let generator = OspGenerator::new(&program).unwrap();
+
+        let finished = generator.finish().unwrap();
+        match &finished.ending {
+            Ending::Returned(outputs) => assert!(matches!(outputs[..], [Value::I64(42)])),
+            Ending::Trapped(trap) => panic!("program trapped: {}", trap),
+        }
+
+        // the proof of each step, the calls of the stubs included, runs to the
+        // pre state of the next step.
+        let mut execution = generator.execution().unwrap();
+        let inputs = (0..finished.steps)
+            .map(|step| execution.osp_input(step).unwrap())
+            .collect::<Vec<_>>();
+        for (step, pair) in inputs.windows(2).enumerate() {
+            assert!(pair[0].trap.is_none());
+            assert_eq!(
+                pair[0].post_state, pair[1].pre_state,
+                "post state of step {}",
+                step
+            );
+        }
+    }
+}
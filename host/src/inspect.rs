@@ -0,0 +1,272 @@
+use std::{fmt::Write as _, fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use bonsai_starter_codec::{program_id, OspRequest};
+use clap::ValueEnum;
+use codec::DecodeAll;
+use ethabi::{short_signature, ParamType};
+use serde::Serialize;
+use wasmi::proof::{CodeProof, OspProof};
+
+use crate::osp::{OspConfig, OspHasher};
+
+/// The format to print the inspected proof.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum InspectFormat {
+    /// human-readable text
+    Text,
+    /// json object
+    Json,
+}
+
+/// The kind of the inspected data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataKind {
+    /// the SCALE encoded osp proof
+    Proof,
+    /// the guest input `abi.encode(instRoot, funcRoot, proof)`
+    Input,
+    /// the calldata of `executeOneStep(bytes32,bytes)`
+    Calldata,
+}
+
+/// The decoded osp proof, with the code roots and the program id if known
+/// from the data.
+#[derive(Debug, Serialize)]
+pub struct Inspected {
+    pub kind: DataKind,
+    pub program_id: Option<String>,
+    pub inst_root: Option<String>,
+    pub func_root: Option<String>,
+    pub proof_len: usize,
+    /// the instruction to execute
+    pub inst: String,
+    /// the state hash of the proof
+    pub pre_state: String,
+    /// the state hash after running the proof, only if the code roots are
+    /// known
+    pub post_state: Option<String>,
+    /// the error of running the proof
+    pub run_error: Option<String>,
+    /// the pc of the instruction
+    pub pc: u32,
+    /// the parts of the proof
+    pub proof: ProofParts,
+    /// the whole proof in the debug format
+    #[serde(skip)]
+    pub proof_debug: String,
+}
+
+/// The parts of the osp proof, each in its debug format.
+#[derive(Debug, Serialize)]
+pub struct ProofParts {
+    /// the instruction at the pc, with its merkle proof
+    pub inst: String,
+    /// the value stack
+    pub stack: String,
+    /// the call frames
+    pub frames: String,
+    /// the memory pages read or written by the instruction
+    pub memory: String,
+    /// the globals
+    pub globals: String,
+}
+
+impl ProofParts {
+    fn new(osp_proof: &OspProof<OspConfig>) -> Self {
+        Self {
+            inst: format!("{:?}", osp_proof.inst_proof),
+            stack: format!("{:?}", osp_proof.value_proof),
+            frames: format!("{:?}", osp_proof.call_proof),
+            memory: format!("{:?}", osp_proof.mem_proof),
+            globals: format!("{:?}", osp_proof.global_proof),
+        }
+    }
+}
+
+fn to_hex(bytes: impl AsRef<[u8]>) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// Read the data as a hex string, or from the file of the path, in hex or
+/// binary as emitted by `--emit-format`.
+pub fn read_data(data: &str) -> Result<Vec<u8>> {
+    let bytes = if Path::new(data).is_file() {
+        fs::read(data).with_context(|| format!("read {}", data))?
+    } else {
+        data.as_bytes().to_vec()
+    };
+
+    match std::str::from_utf8(&bytes) {
+        Ok(text) if text.trim().starts_with("0x") || hex::decode(text.trim()).is_ok() => {
+            hex::decode(text.trim().trim_start_matches("0x")).context("invalid hex data")
+        }
+        _ => Ok(bytes),
+    }
+}
+
+/// The osp proof in the data, with the code roots and the program id if known.
+struct ProofData {
+    kind: DataKind,
+    roots: Option<([u8; 32], [u8; 32])>,
+    program_id: Option<[u8; 32]>,
+    proof: Vec<u8>,
+}
+
+/// Split the data into the osp proof and the code roots if known, by trying
+/// the calldata, the guest input and the raw proof in order.
+fn split_data(data: &[u8]) -> Result<ProofData> {
+    let selector = short_signature(
+        "executeOneStep",
+        &[ParamType::FixedBytes(32), ParamType::Bytes],
+    );
+    if let Some(args) = data.strip_prefix(&selector[..]) {
+        let mut tokens = ethabi::decode(&[ParamType::FixedBytes(32), ParamType::Bytes], args)
+            .context("invalid executeOneStep calldata")?
+            .into_iter();
+        let program_id = tokens
+            .next()
+            .and_then(|token| token.into_fixed_bytes())
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| anyhow!("invalid program id"))?;
+        let proof = tokens
+            .next()
+            .and_then(|token| token.into_bytes())
+            .ok_or_else(|| anyhow!("invalid proof"))?;
+        return Ok(ProofData {
+            kind: DataKind::Calldata,
+            roots: None,
+            program_id: Some(program_id),
+            proof,
+        });
+    }
+
+    if let Ok(request) = OspRequest::decode(data) {
+        return Ok(ProofData {
+            kind: DataKind::Input,
+            roots: Some((request.inst_root, request.func_root)),
+            program_id: Some(program_id(request.inst_root, request.func_root)),
+            proof: request.proof,
+        });
+    }
+
+    Ok(ProofData {
+        kind: DataKind::Proof,
+        roots: None,
+        program_id: None,
+        proof: data.to_vec(),
+    })
+}
+
+fn decode_proof(proof: &[u8]) -> Result<OspProof<OspConfig>> {
+    OspProof::<OspConfig>::decode_all(&mut &*proof)
+        .map_err(|err| anyhow!("invalid osp proof: {}", err))
+}
+
+/// Decode the osp proof in the raw proof, the guest input or the
+/// `executeOneStep` calldata, and run it if the code roots are known.
+pub fn inspect(data: &[u8]) -> Result<Inspected> {
+    let ProofData {
+        kind,
+        roots,
+        program_id,
+        proof,
+    } = split_data(data)?;
+
+    let osp_proof = decode_proof(&proof)?;
+    let pre_state = osp_proof.hash();
+
+    let (mut post_state, mut run_error) = (None, None);
+    if let Some((inst_root, func_root)) = roots {
+        let code_proof = CodeProof::<OspHasher> {
+            inst_root,
+            func_root,
+        };
+        let mut osp_proof = decode_proof(&proof)?;
+        match osp_proof.run(&code_proof) {
+            Ok(_) => post_state = Some(to_hex(osp_proof.hash())),
+            Err(err) => run_error = Some(format!("{:?}", err)),
+        }
+    }
+
+    Ok(Inspected {
+        kind,
+        program_id: program_id.map(to_hex),
+        inst_root: roots.map(|(inst_root, _)| to_hex(inst_root)),
+        func_root: roots.map(|(_, func_root)| to_hex(func_root)),
+        proof_len: proof.len(),
+        inst: format!("{:?}", osp_proof.inst_proof.inst),
+        pre_state: to_hex(pre_state),
+        post_state,
+        run_error,
+        pc: osp_proof.inst_proof.pc,
+        proof: ProofParts::new(&osp_proof),
+        proof_debug: format!("{:#?}", osp_proof),
+    })
+}
+
+impl Inspected {
+    pub fn format(&self, format: InspectFormat) -> Result<String> {
+        match format {
+            InspectFormat::Json => Ok(serde_json::to_string_pretty(self)?),
+            InspectFormat::Text => {
+                let mut text = String::new();
+                writeln!(text, "kind:       {:?}", self.kind)?;
+                for (name, value) in [
+                    ("program id", &self.program_id),
+                    ("inst root", &self.inst_root),
+                    ("func root", &self.func_root),
+                ] {
+                    if let Some(value) = value {
+                        writeln!(text, "{:<11} {}", format!("{}:", name), value)?;
+                    }
+                }
+                writeln!(text, "proof len:  {}", self.proof_len)?;
+                writeln!(text, "pc:         {}", self.pc)?;
+                writeln!(text, "inst:       {}", self.inst)?;
+                writeln!(text, "pre state:  {}", self.pre_state)?;
+                if let Some(post_state) = &self.post_state {
+                    writeln!(text, "post state: {}", post_state)?;
+                }
+                if let Some(run_error) = &self.run_error {
+                    writeln!(text, "run error:  {}", run_error)?;
+                }
+                writeln!(text, "proof:\n{}", self.proof_debug)?;
+                Ok(text)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        call::Call,
+        imports::StubImports,
+        osp::{ModuleSource, OspGenerator, Program},
+    };
+
+    #[test]
+    fn inspect_pc_of_fib_proof() {
+        let program = Program {
+            source: ModuleSource::Builtin,
+            imports: StubImports::default(),
+            call: Call::fib(),
+            run_start: false,
+        };
+        let generator = OspGenerator::new(&program).unwrap();
+        let last = generator.last_step().unwrap();
+
+        for step in [0, 1, last / 2, last] {
+            let pc = generator.execution().unwrap().seek(step).unwrap().unwrap();
+            let input = generator.gen_osp_input(step).unwrap();
+
+            let inspected = inspect(&input.proof).unwrap();
+            assert_eq!(inspected.pc, pc, "pc of step {}", step);
+            assert_eq!(inspected.kind, DataKind::Proof);
+            assert_eq!(inspected.pre_state, to_hex(input.pre_state));
+        }
+    }
+}
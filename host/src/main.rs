@@ -1,25 +1,201 @@
-use bonsai_starter_methods::{OSPPROOF_ELF, OSPPROOF_ID};
-use clap::Parser;
-use ethabi::{ethereum_types::H256, ParamType};
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
 use risc0_zkvm::default_executor_from_elf;
 
+mod batch;
+mod call;
+mod dispute;
+mod emit;
+mod imports;
+mod inspect;
 mod osp;
 mod raw;
+mod receipt;
+mod start;
+mod trace;
+use batch::{gen_step_proofs, Steps};
+use call::{Call, CallArg};
+use dispute::{bisect, parse_hash, ClaimedSteps, Prompt, StateHash};
+use emit::{emit, EmitFormat, Emitted};
+use imports::StubImports;
+use inspect::{inspect, read_data, InspectFormat};
 use osp::*;
+use receipt::{
+    check_journal, decode_journal, fmt_journal, load_receipt, save_receipt, verify_receipt,
+    GuestJournal, ReceiptFormat,
+};
+use trace::trace;
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Bisect the steps against the state hashes claimed by a counterparty,
+    /// and print the osp proof of the first diverging step
+    Dispute {
+        /// the step of the claimed final states, the total steps of the
+        /// program for the state after its last step
+        #[arg(long)]
+        end: u64,
+
+        /// our claimed state hash at `end`, checked against the execution
+        #[arg(long, value_parser = parse_hash)]
+        ours: Option<StateHash>,
+
+        /// the counterparty's claimed state hash at `end`
+        #[arg(long, value_parser = parse_hash)]
+        theirs: StateHash,
+
+        /// the json lines of the counterparty's state hashes generated by
+        /// `--steps`, ask them on the terminal if not set
+        #[arg(long)]
+        claims: Option<PathBuf>,
+    },
+    /// Verify a stored receipt against the image id, and print its journal
+    Verify {
+        /// the receipt saved by `--receipt-out`
+        receipt: PathBuf,
+
+        /// the guest which generated the receipt
+        #[arg(long, value_enum, default_value_t = Guest::OneStep)]
+        guest: Guest,
+    },
+    /// Decode an osp proof, and print its instruction, state and hash
+    Inspect {
+        /// the raw proof, the guest input or the `executeOneStep` calldata,
+        /// as a hex string or a file of hex or binary
+        data: String,
+
+        /// the output format
+        #[arg(long, value_enum, default_value_t = InspectFormat::Text)]
+        format: InspectFormat,
+    },
+    /// Execute the program until it finishes, and print its total steps and
+    /// outputs, or the trap which ends it
+    Length,
+    /// Trace the pc, the inst and the state hash of the steps as json lines,
+    /// until the program finishes
+    Trace {
+        /// the first step to trace
+        #[arg(long, default_value_t = 0)]
+        from: u64,
+
+        /// the last step to trace, trace until the program finishes if not set
+        #[arg(long)]
+        to: Option<u64>,
+
+        /// trace every `k` steps
+        #[arg(
+            long,
+            value_name = "K",
+            default_value_t = 1,
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        every: u64,
+
+        /// write the trace to the file instead of stdout
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+    },
+}
 
 /// Args for prove
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// the inst step for executor osp proof
+    #[arg(short, long, required_unless_present_any = ["steps", "last"])]
+    step: Option<u64>,
+
+    /// generate the osp proofs and state hashes for the steps as json lines,
+    /// without proving, such as `100..200`, `100..=200` or `5,17,99`
+    #[arg(long, conflicts_with_all = ["step", "last"])]
+    steps: Option<Steps>,
+
+    /// prove the last step, whose inst finishes the program
+    #[arg(long, default_value = "false", conflicts_with = "step")]
+    last: bool,
+
+    /// the `.wasm` or `.wat` module to execute, use the built-in fib module
+    /// if not set
     #[arg(short, long)]
-    step: u64,
+    module: Option<PathBuf>,
+
+    /// the exported function to call
+    #[arg(short, long, default_value = "fib")]
+    func: String,
+
+    /// the args to call the function with, as `<type>:<value>` such as
+    /// `i64:42`, can be repeated. Defaults to `i32:10` for the built-in fib
+    /// module
+    #[arg(short, long = "arg", value_name = "TYPE:VALUE")]
+    args: Vec<CallArg>,
+
+    /// the json manifest of the stub host functions for the module imports
+    #[arg(short, long)]
+    imports: Option<PathBuf>,
+
+    /// run the start function of the module before the call, the step is
+    /// counted from the instantiation
+    #[arg(long, default_value = "false")]
+    run_start: bool,
+
+    /// write the guest input `abi.encode(instRoot, funcRoot, proof)`, or
+    /// `abi.encode(instRoot, funcRoot, proofs)` with `--multi`, to the file,
+    /// or stdout if `-`, instead of proving
+    #[arg(long, value_name = "PATH")]
+    emit_input: Option<PathBuf>,
+
+    /// write the calldata of `executeOneStep(bytes32,bytes)`, or
+    /// `executeSteps(bytes32,bytes[])` with `--multi`, to the file, or stdout
+    /// if `-`, instead of proving
+    #[arg(long, value_name = "PATH")]
+    emit_calldata: Option<PathBuf>,
+
+    /// the format of the emitted input and calldata
+    #[arg(long, value_enum, default_value_t = EmitFormat::Hex)]
+    emit_format: EmitFormat,
+
+    /// execute the `n` consecutive steps from `step` in one guest run, by
+    /// the multi step guest
+    #[arg(long, value_name = "N")]
+    multi: Option<u64>,
+
+    /// only execute the guest without proving, and report the journal, the
+    /// segments and their cycles padded to a power of two
+    #[arg(long, default_value = "false")]
+    execute_only: bool,
+
+    /// save the receipt to the file
+    #[arg(long, value_name = "PATH", conflicts_with = "execute_only")]
+    receipt_out: Option<PathBuf>,
+
+    /// the format of the saved receipt
+    #[arg(long, value_enum, default_value_t = ReceiptFormat::Bincode)]
+    receipt_format: ReceiptFormat,
 
     /// if show debug log
     #[arg(short, long, default_value = "false")]
     debug: bool,
 }
 
+/// Exit with how the program finished if the step is past its end, as there
+/// is no inst to prove.
+fn exit_if_finished<T>(res: anyhow::Result<T>) -> T {
+    match res {
+        Ok(value) => value,
+        Err(err) => match err.downcast_ref::<Finished>() {
+            Some(finished) => {
+                log::error!("no inst to prove: {}", finished);
+                std::process::exit(1);
+            }
+            None => panic!("gen osp input failed: {:?}", err),
+        },
+    }
+}
+
 fn main() {
     let args = Args::parse();
     let log_level = if args.debug {
@@ -34,42 +210,205 @@ fn main() {
         .filter_level(log_level)
         .try_init();
 
-    let env = create_env(args.step).expect("create env failed");
+    if let Some(Command::Verify { receipt, guest }) = &args.command {
+        let receipt = load_receipt(receipt).expect("load receipt failed");
+        let journal = verify_receipt(&receipt, *guest).expect("verify receipt failed");
+        log::info!("journal result: {}", fmt_journal(&journal));
+        println!("{}", fmt_journal(&journal));
+        return;
+    }
+
+    if let Some(Command::Inspect { data, format }) = &args.command {
+        let data = read_data(data).expect("read data failed");
+        let inspected = inspect(&data).expect("inspect failed");
+        println!("{}", inspected.format(*format).expect("format failed"));
+        return;
+    }
+
+    let source = match &args.module {
+        Some(path) => ModuleSource::from_file(path).expect("load module failed"),
+        None => ModuleSource::Builtin,
+    };
+
+    let imports = match &args.imports {
+        Some(path) => StubImports::from_file(path).expect("load imports failed"),
+        None => StubImports::default(),
+    };
+
+    let call = if args.module.is_none() && args.args.is_empty() {
+        Call::fib()
+    } else {
+        Call {
+            func: args.func,
+            args: args.args,
+        }
+    };
+
+    let program = Program {
+        source,
+        imports,
+        call,
+        run_start: args.run_start,
+    };
+
+    if let Some(Command::Dispute {
+        end,
+        ours,
+        theirs,
+        claims,
+    }) = &args.command
+    {
+        let divergence = match claims {
+            Some(path) => {
+                let mut claims = ClaimedSteps::from_file(path).expect("load claims failed");
+                bisect(&program, &mut claims, *end, *ours, *theirs)
+            }
+            None => bisect(&program, &mut Prompt, *end, *ours, *theirs),
+        }
+        .expect("dispute failed");
+
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&divergence).expect("encode divergence failed")
+        );
+        return;
+    }
+
+    if let Some(Command::Length) = &args.command {
+        let finished = OspGenerator::new(&program)
+            .and_then(|generator| generator.finish())
+            .expect("execute program failed");
+        log::info!("{}", finished);
+
+        let (outputs, trap) = match &finished.ending {
+            Ending::Returned(outputs) => (
+                outputs
+                    .iter()
+                    .map(|output| format!("{:?}", output))
+                    .collect::<Vec<_>>(),
+                None,
+            ),
+            Ending::Trapped(trap) => (vec![], Some(trap)),
+        };
+        println!(
+            "{}",
+            serde_json::json!({
+                "steps": finished.steps,
+                "last_step": finished.steps.checked_sub(1),
+                "outputs": outputs,
+                "trap": trap,
+            })
+        );
+        return;
+    }
+
+    if let Some(Command::Trace {
+        from,
+        to,
+        every,
+        out,
+    }) = &args.command
+    {
+        let traced = match out {
+            Some(path) => {
+                let file = std::fs::File::create(path).expect("create trace file failed");
+                trace(&program, *from, *to, *every, std::io::BufWriter::new(file))
+            }
+            None => trace(&program, *from, *to, *every, std::io::stdout().lock()),
+        }
+        .expect("trace failed");
+        log::info!("traced {} steps", traced);
+        return;
+    }
+
+    if let Some(steps) = &args.steps {
+        gen_step_proofs(&program, steps, std::io::stdout().lock()).expect("gen step proofs failed");
+        return;
+    }
+
+    let step = match args.step {
+        Some(step) => step,
+        None => OspGenerator::new(&program)
+            .and_then(|generator| generator.last_step())
+            .expect("find the last step failed"),
+    };
+    let (guest, data, calldata, expected, emitted) = match args.multi {
+        Some(n) => {
+            let input = exit_if_finished(gen_osp_multi_input(&program, step, n));
+            let emitted = Emitted::steps(&input);
+            let (data, calldata) = (input.input(), input.calldata());
+            let expected = GuestJournal::MultiStep(input.journal());
+            (Guest::MultiStep, data, calldata, expected, emitted)
+        }
+        None => {
+            let input = exit_if_finished(gen_osp_input(&program, step));
+            let emitted = Emitted::step(&input);
+            let (data, calldata) = (input.input(), input.calldata());
+            let expected = GuestJournal::OneStep(input.journal());
+            (Guest::OneStep, data, calldata, expected, emitted)
+        }
+    };
+
+    if let Some(path) = &args.emit_input {
+        emit(path, args.emit_format, &emitted, &data).expect("emit input failed");
+    }
+    if let Some(path) = &args.emit_calldata {
+        emit(path, args.emit_format, &emitted, &calldata).expect("emit calldata failed");
+    }
+    // the emitted proof is proven by Bonsai, or locally by another run.
+    if args.emit_input.is_some() || args.emit_calldata.is_some() {
+        return;
+    }
+
+    let env = create_env(&data).expect("create env failed");
 
     // Next, we make an executor, loading the (renamed) ELF binary.
-    let mut exec = default_executor_from_elf(env, OSPPROOF_ELF).unwrap();
+    let mut exec = default_executor_from_elf(env, guest.elf()).unwrap();
 
     // Run the executor to produce a session.
     let session = exec.run().unwrap();
 
+    if args.execute_only {
+        let journal = decode_journal(guest, &session.journal).expect("decode journal failed");
+        if let Err(mismatch) = check_journal(&expected, &journal) {
+            log::error!("{}", mismatch);
+            std::process::exit(1);
+        }
+        // the segments are padded to a power of two, which is the proving cost
+        // rather than the executed cycles.
+        let padded_cycles = session
+            .segments
+            .iter()
+            .map(|segment| segment.resolve().map(|segment| 1u64 << segment.po2))
+            .sum::<anyhow::Result<u64>>()
+            .expect("resolve segments failed");
+
+        log::info!(
+            "executed {} segments, {} padded cycles",
+            session.segments.len(),
+            padded_cycles
+        );
+        log::info!("journal result: {}", fmt_journal(&journal));
+        return;
+    }
+
     // Prove the session to produce a receipt.
     let receipt = session.prove().unwrap();
 
     // Optional: Verify receipt to confirm that recipients will also be able to
     // verify your receipt
-    receipt.verify(OSPPROOF_ID).unwrap();
+    let journal = verify_receipt(&receipt, guest).expect("verify receipt failed");
+    if let Err(mismatch) = check_journal(&expected, &journal) {
+        log::error!("{}", mismatch);
+        std::process::exit(1);
+    }
 
     log::trace!("receipt {:?}", receipt);
 
-    // function storeResult(bytes32 preState, bytes32 postState)
-    let output = ethabi::decode_whole(
-        &[ParamType::FixedBytes(32), ParamType::FixedBytes(32)],
-        &receipt.journal,
-    )
-    .expect("decode journal failed");
-
-    let per_hash = H256::from_slice(
-        &output[0]
-            .clone()
-            .into_fixed_bytes()
-            .expect("decode pre-hash failed"),
-    );
-    let post_hash = H256::from_slice(
-        &output[1]
-            .clone()
-            .into_fixed_bytes()
-            .expect("decode post-hash failed"),
-    );
-
-    log::info!("journal result: {:?} -> {:?}", per_hash, post_hash);
+    if let Some(path) = &args.receipt_out {
+        save_receipt(&receipt, path, args.receipt_format).expect("save receipt failed");
+        log::info!("receipt saved to {}", path.display());
+    }
+
+    log::info!("journal result: {}", fmt_journal(&journal));
 }
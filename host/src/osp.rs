@@ -1,95 +1,564 @@
-use anyhow::Result;
+use std::{fmt, fs, path::Path};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use bonsai_starter_codec::{program_id, OspJournal, OspMultiJournal, OspMultiRequest, OspRequest};
+#[cfg(not(feature = "sha256"))]
+pub use bonsai_starter_merkle::MerkleKeccak256 as OspHasher;
+#[cfg(feature = "sha256")]
+pub use bonsai_starter_merkle::MerkleSha256 as OspHasher;
+#[cfg(not(feature = "sha256"))]
+use bonsai_starter_methods::{
+    OSPPROOF_ELF as ONE_STEP_ELF, OSPPROOF_ID as ONE_STEP_ID, OSPPROOF_MULTI_ELF as MULTI_STEP_ELF,
+    OSPPROOF_MULTI_ID as MULTI_STEP_ID,
+};
+#[cfg(feature = "sha256")]
+use bonsai_starter_methods::{
+    OSPPROOF_MULTI_SHA256_ELF as MULTI_STEP_ELF, OSPPROOF_MULTI_SHA256_ID as MULTI_STEP_ID,
+    OSPPROOF_SHA256_ELF as ONE_STEP_ELF, OSPPROOF_SHA256_ID as ONE_STEP_ID,
+};
+use clap::ValueEnum;
 use codec::Encode;
-use ethabi::{ethereum_types::H256, Token};
+use ethabi::{ethereum_types::H256, short_signature, ParamType, Token};
 use risc0_zkvm::ExecutorEnv;
 use wasmi::{
     core::Value,
-    merkle::{DefaultMemoryConfig, MerkleKeccak256},
+    merkle::DefaultMemoryConfig,
     proof::{CodeProof, OspProof},
-    AsContextMut, Engine, Error, Extern, Instance, Linker, Module, StepResult, Store,
+    AsContextMut, Engine, Error, Extern, Func, Instance, Linker, Module, StepResult, Store,
 };
 use wat::parse_str;
 
-use crate::raw::FIB;
+use crate::{
+    call::Call,
+    imports::{HostImports, StubImports},
+    raw::FIB,
+    start::{export_start, START_EXPORT},
+};
 
-pub type EthConfig = DefaultMemoryConfig<MerkleKeccak256>;
+/// The memory config of the osp proofs, by the Keccak-256 merkle, or the
+/// SHA-256 merkle with the `sha256` feature.
+pub type OspConfig = DefaultMemoryConfig<OspHasher>;
 
-#[allow(dead_code)]
-fn setup_module_from_wat<T>(store: &mut Store<T>, wat: impl AsRef<str>) -> Result<Module, Error> {
+fn setup_module_from_wat(engine: &Engine, wat: impl AsRef<str>) -> Result<Module, Error> {
     let wasm = parse_str(wat).expect("Illegal wat");
-    Module::new(store.engine(), &wasm[..])
+    Module::new(engine, &wasm[..])
+}
+
+fn setup_module_from_wasm(engine: &Engine, code: &[u8]) -> Result<Module, Error> {
+    Module::new(engine, code)
 }
 
-#[allow(dead_code)]
-fn setup_module_from_wasm<T>(store: &mut Store<T>, code: &[u8]) -> Result<Module, Error> {
-    Module::new(store.engine(), code)
+/// The wasm code to generate osp proof for.
+pub enum ModuleSource {
+    /// The built-in fib module, see [`FIB`].
+    Builtin,
+    /// A `.wasm` or `.wat` module loaded from file, `.wat` is parsed to wasm
+    /// binary when loading.
+    Wasm(Vec<u8>),
 }
 
-fn instantiate<T>(store: &mut Store<T>, module: &Module) -> Result<Instance, Error> {
-    let linker = <Linker<T>>::new();
+impl ModuleSource {
+    /// Load the module from `path`, the format is detected by the content
+    /// instead of the file extension.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let code = fs::read(path).with_context(|| format!("read module {}", path.display()))?;
+
+        if code.starts_with(b"\0asm") {
+            return Ok(Self::Wasm(code));
+        }
+
+        let wat = String::from_utf8(code)
+            .with_context(|| format!("module {} is neither wasm nor wat", path.display()))?;
+        let code = parse_str(wat).with_context(|| format!("parse wat {}", path.display()))?;
+        Ok(Self::Wasm(code))
+    }
+
+    /// Setup the module, returns if the start function is exported as
+    /// [`START_EXPORT`] to run by step call, see [`export_start`].
+    fn setup(&self, engine: &Engine, run_start: bool) -> Result<(Module, bool)> {
+        let res = match self {
+            Self::Builtin => (setup_module_from_wat(engine, FIB)?, false),
+            Self::Wasm(code) if run_start => match export_start(code)? {
+                Some(code) => (setup_module_from_wasm(engine, &code)?, true),
+                None => (setup_module_from_wasm(engine, code)?, false),
+            },
+            Self::Wasm(code) => (setup_module_from_wasm(engine, code)?, false),
+        };
+        Ok(res)
+    }
+}
+
+/// The wasm program to generate osp proof for.
+pub struct Program<I = StubImports> {
+    pub source: ModuleSource,
+    pub imports: I,
+    pub call: Call,
+    /// run the start function before the call, so the steps are counted from
+    /// the instantiation
+    pub run_start: bool,
+}
+
+fn instantiate<T>(
+    store: &mut Store<T>,
+    module: &Module,
+    imports: &impl HostImports<T>,
+) -> Result<Instance> {
+    let mut linker = <Linker<T>>::new();
+    imports.define(store, &mut linker, module)?;
     let pre = linker.instantiate(store.as_context_mut(), module)?;
     let instance = pre.ensure_no_start(store.as_context_mut())?;
     Ok(instance)
 }
 
+fn get_func<T>(store: &mut Store<T>, instance: Instance, name: &str) -> Result<Func> {
+    instance
+        .get_export(store.as_context_mut(), name)
+        .and_then(Extern::into_func)
+        .ok_or_else(|| anyhow!("could not find export function `{}`", name))
+}
+
+/// Check the args against the signature of the function, and make the output
+/// slots for its results.
+fn prepare_call<T>(store: &mut Store<T>, func: Func, call: &Call) -> Result<Vec<Value>> {
+    let func_type = func.func_type(store.as_context_mut());
+
+    let params = call
+        .args
+        .iter()
+        .map(|arg| arg.value_type())
+        .collect::<Vec<_>>();
+    ensure!(
+        params == func_type.params(),
+        "function `{}` expects params {:?}, got {:?}",
+        call.func,
+        func_type.params(),
+        params
+    );
+
+    Ok(func_type
+        .results()
+        .iter()
+        .copied()
+        .map(Value::default)
+        .collect())
+}
+
 fn call_step<T>(
     store: &mut Store<T>,
-    instance: Instance,
-    name: &str,
+    func: Func,
     inputs: &[Value],
     outputs: &mut [Value],
     n: Option<&mut u64>,
 ) -> Result<StepResult<()>, Error> {
-    let f = instance
-        .get_export(store.as_context_mut(), name)
-        .and_then(Extern::into_func)
-        .expect("Could find export function");
+    func.step_call(store.as_context_mut(), inputs, outputs, n)
+}
+
+/// Resume the step call of `func` which ran out of steps, from the frames
+/// and the stack kept in the store.
+fn resume_step<T>(
+    store: &mut Store<T>,
+    func: Func,
+    outputs: &mut [Value],
+    n: Option<&mut u64>,
+) -> Result<StepResult<()>, Error> {
+    func.step_resume(store.as_context_mut(), outputs, n)
+}
 
-    f.step_call(store.as_context_mut(), inputs, outputs, n)
+/// The osp proof generator of a program.
+///
+/// The module is compiled once and shared by the executions, see
+/// [`Execution`] to generate the proofs of many steps on one store.
+pub struct OspGenerator<'a, I> {
+    program: &'a Program<I>,
+    engine: Engine,
+    module: Module,
+    has_start: bool,
 }
 
-fn gen_osp_proof_for_test(
-    mut steps: u64,
-) -> Result<(OspProof<EthConfig>, CodeProof<MerkleKeccak256>)> {
-    let engine = Engine::default();
-    let mut store = Store::new(&engine, ());
-    let module = setup_module_from_wat(&mut store, FIB).unwrap();
-    let instance = instantiate(&mut store, &module).unwrap();
+impl<'a, I: HostImports<()>> OspGenerator<'a, I> {
+    pub fn new(program: &'a Program<I>) -> Result<Self> {
+        let engine = Engine::default();
+        let (module, has_start) = program.source.setup(&engine, program.run_start)?;
+
+        Ok(Self {
+            program,
+            engine,
+            module,
+            has_start,
+        })
+    }
+
+    /// Instantiate the program on a fresh store, before its first step.
+    pub fn execution(&self) -> Result<Execution<'_, 'a, I>> {
+        let mut store = Store::new(&self.engine, ());
+        let instance = instantiate(&mut store, &self.module, &self.program.imports)?;
+        let (make_proof, code_proof) = osp_proof_maker(&mut store, instance);
+        let call = &self.program.call;
+
+        let func = get_func(&mut store, instance, &call.func)?;
+        let outputs = prepare_call(&mut store, func, call)?;
+        let (phase, start_steps) = if self.has_start {
+            let start = get_func(&mut store, instance, START_EXPORT)?;
+            (Phase::Start(start), None)
+        } else {
+            (Phase::Call, Some(0))
+        };
+
+        Ok(Execution {
+            generator: self,
+            store,
+            make_proof,
+            code_roots: (code_proof.inst_root, code_proof.func_root),
+            func,
+            inputs: call.inputs(),
+            outputs,
+            step: 0,
+            start_steps,
+            state: ExecState::Ready(phase),
+        })
+    }
 
-    let code_merkle = store
-        .code_proof::<MerkleKeccak256>(instance)
-        .make_code_merkle();
+    /// Generate the osp proof of the inst at `step`, and run it to get the
+    /// post state, or the trap if the inst traps.
+    pub fn gen_osp_input(&self, step: u64) -> Result<OspInput> {
+        self.execution()?.osp_input(step)
+    }
+
+    /// Execute the program until it finishes, to get its total steps and
+    /// outputs.
+    pub fn finish(&self) -> Result<Finished> {
+        self.execution()?.finish()
+    }
+
+    /// The last step of the program, whose inst finishes it.
+    pub fn last_step(&self) -> Result<u64> {
+        let finished = self.finish()?;
+        finished
+            .steps
+            .checked_sub(1)
+            .ok_or_else(|| anyhow!("program finished without any step"))
+    }
+}
+
+/// The function step called by the execution.
+#[derive(Clone, Copy)]
+enum Phase {
+    /// the start function exported as [`START_EXPORT`]
+    Start(Func),
+    /// the entry function of the [`Call`]
+    Call,
+}
+
+enum ExecState {
+    /// the function of the phase is not yet called
+    Ready(Phase),
+    /// the function of the phase ran out of steps before the inst at pc
+    Paused(Phase, u32),
+    Finished(Finished),
+}
+
+/// The execution of the program on one store, which only moves forward, so
+/// the proofs of the ascending steps cost the steps to the last one once.
+pub struct Execution<'g, 'a, I> {
+    generator: &'g OspGenerator<'a, I>,
+    store: Store<()>,
+    /// makes the osp proofs on the code merkle built at the instantiation
+    make_proof: ProofMaker,
+    /// the inst root and the func root of the instance
+    code_roots: ([u8; 32], [u8; 32]),
+    func: Func,
+    inputs: Vec<Value>,
+    outputs: Vec<Value>,
+    /// the steps executed, the next inst is at this step
+    step: u64,
+    /// the steps of the start function, known once it returns
+    start_steps: Option<u64>,
+    state: ExecState,
+}
+
+impl<'g, 'a, I: HostImports<()>> Execution<'g, 'a, I> {
+    /// Execute to the inst at `step`, returns its pc, or how the program
+    /// finished if within `step`. Restarts from a fresh store if `step` is
+    /// behind.
+    pub fn seek(&mut self, step: u64) -> Result<Result<u32, Finished>> {
+        if step < self.step {
+            *self = self.generator.execution()?;
+        }
+
+        loop {
+            let mut left = step - self.step;
+            let (phase, res) = match self.state {
+                ExecState::Finished(ref finished) => return Ok(Err(finished.clone())),
+                ExecState::Paused(_, pc) if left == 0 => return Ok(Ok(pc)),
+                ExecState::Paused(phase, _) => {
+                    let (func, outputs): (Func, &mut [Value]) = match phase {
+                        Phase::Start(start) => (start, &mut []),
+                        Phase::Call => (self.func, &mut self.outputs),
+                    };
+                    let res = resume_step(&mut self.store, func, outputs, Some(&mut left));
+                    (phase, res)
+                }
+                ExecState::Ready(phase @ Phase::Start(start)) => {
+                    let res = call_step(&mut self.store, start, &[], &mut [], Some(&mut left));
+                    (phase, res)
+                }
+                ExecState::Ready(Phase::Call) => {
+                    let res = call_step(
+                        &mut self.store,
+                        self.func,
+                        &self.inputs,
+                        &mut self.outputs,
+                        Some(&mut left),
+                    );
+                    (Phase::Call, res)
+                }
+            };
+            // the inst which returns or traps is counted as a step.
+            self.step = step - left;
+
+            self.state = match (phase, res) {
+                (_, Ok(StepResult::RunOutOfStep(pc))) => ExecState::Paused(phase, pc),
+                (Phase::Start(_), Ok(StepResult::Results(()))) => {
+                    log::debug!("start function returned after {} steps", self.step);
+                    self.start_steps = Some(self.step);
+                    ExecState::Ready(Phase::Call)
+                }
+                (Phase::Call, Ok(StepResult::Results(()))) => ExecState::Finished(Finished {
+                    steps: self.step,
+                    ending: Ending::Returned(self.outputs.clone()),
+                }),
+                (_, Err(Error::Trap(trap))) => ExecState::Finished(Finished {
+                    steps: self.step,
+                    ending: Ending::Trapped(trap.to_string()),
+                }),
+                (_, Err(err)) => return Err(err.into()),
+            };
+        }
+    }
+
+    /// If the inst at `step` returns from the start function. The call is then
+    /// entered with its frame and args, so the post state of the step is not
+    /// the state at `step + 1`, and no osp proof bridges the two.
+    pub fn returns_from_start(&mut self, step: u64) -> Result<bool> {
+        // the start function has not returned by the executed steps.
+        if self.start_steps.is_none() && self.step <= step {
+            self.seek(step + 1)?;
+        }
+        Ok(self.start_steps == Some(step + 1))
+    }
+
+    /// Generate the osp proof of the inst at `step` with its pc, or how the
+    /// program finished if within `step`.
+    pub fn step_proof(
+        &mut self,
+        step: u64,
+    ) -> Result<Result<(u32, OspProof<OspConfig>), Finished>> {
+        let pc = match self.seek(step)? {
+            Ok(pc) => pc,
+            Err(finished) => return Ok(Err(finished)),
+        };
+        let osp_proof = (self.make_proof)(&mut self.store, pc)?;
+        Ok(Ok((pc, osp_proof)))
+    }
+
+    /// Generate the osp proof of the inst at `step`, fails with [`Finished`]
+    /// if the program finishes within `step`.
+    pub fn osp_proof(&mut self, step: u64) -> Result<(OspProof<OspConfig>, CodeProof<OspHasher>)> {
+        let pc = self.seek(step)??;
+        let osp_proof = (self.make_proof)(&mut self.store, pc)?;
+
+        log::info!("osp inst: {:?}", osp_proof.inst_proof.inst);
+
+        Ok((osp_proof, self.code_proof()))
+    }
+
+    /// The code proof of the instance, with the roots of its code merkle.
+    pub fn code_proof(&self) -> CodeProof<OspHasher> {
+        let (inst_root, func_root) = self.code_roots;
+        CodeProof {
+            inst_root,
+            func_root,
+        }
+    }
+
+    /// Generate the osp proof of the inst at `step`, and run it to get the
+    /// post state, or the trap if the inst traps.
+    pub fn osp_input(&mut self, step: u64) -> Result<OspInput> {
+        let (mut osp_proof, code_proof) = self.osp_proof(step)?;
+
+        let proof = osp_proof.encode();
+        let pre_state = osp_proof.hash();
+        let (post_state, trap) = match osp_proof.run(&code_proof) {
+            Ok(_) => (osp_proof.hash(), None),
+            Err(Error::Trap(trap)) => {
+                log::info!("step {} traps: {}", step, trap);
+                ([0; 32], Some(trap.to_string()))
+            }
+            Err(err) => {
+                return Err(anyhow!(err).context(format!("run osp proof for step {}", step)))
+            }
+        };
+
+        Ok(OspInput {
+            code_proof,
+            proof,
+            pre_state,
+            post_state,
+            trap,
+        })
+    }
+
+    /// Execute the program until it finishes.
+    pub fn finish(&mut self) -> Result<Finished> {
+        match self.seek(u64::MAX)? {
+            Ok(pc) => bail!("program not finished at pc {}", pc),
+            Err(finished) => Ok(finished),
+        }
+    }
+}
+
+/// How the program ends.
+#[derive(Debug, Clone)]
+pub enum Ending {
+    /// the call returned the outputs
+    Returned(Vec<Value>),
+    /// the inst of the last step trapped
+    Trapped(String),
+}
+
+/// The program finished within the steps, so there is no inst to prove at
+/// the step.
+#[derive(Debug, Clone)]
+pub struct Finished {
+    /// the total steps of the program, the last step is `steps - 1`
+    pub steps: u64,
+    pub ending: Ending,
+}
+
+impl fmt::Display for Finished {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.ending {
+            Ending::Returned(outputs) => write!(
+                f,
+                "program finished after {} steps with outputs {:?}",
+                self.steps, outputs
+            )?,
+            Ending::Trapped(trap) => {
+                write!(f, "program trapped after {} steps: {}", self.steps, trap)?
+            }
+        }
+        write!(f, ", the last step is {}", self.steps.saturating_sub(1))
+    }
+}
 
+impl std::error::Error for Finished {}
+
+/// Make the osp proof of the inst at a pc from the state of the store.
+type ProofMaker = Box<dyn Fn(&mut Store<()>, u32) -> Result<OspProof<OspConfig>>>;
+
+/// Build the code merkle of the instance once, as the code never changes
+/// after the instantiation, and return the maker of the osp proofs on it
+/// with the code proof.
+fn osp_proof_maker(
+    store: &mut Store<()>,
+    instance: Instance,
+) -> (ProofMaker, CodeProof<OspHasher>) {
+    let code_merkle = store.code_proof::<OspHasher>(instance).make_code_merkle();
     let code_proof = code_merkle.code_proof();
 
-    let inputs = vec![Value::I32(10)];
-    let mut outputs = vec![Value::I32(0)];
-
-    let res = call_step(
-        &mut store,
-        instance,
-        "fib",
-        &inputs,
-        &mut outputs,
-        Some(&mut steps),
-    )?;
-
-    let pc = match res {
-        StepResult::Results(()) => unreachable!(),
-        StepResult::RunOutOfStep(pc) => pc,
+    let make_proof = move |store: &mut Store<()>, pc: u32| {
+        let osp_proof = store
+            .osp_proof::<OspConfig>(&code_merkle, instance)
+            .make_osp_proof_v0(pc)?;
+        Ok(osp_proof)
     };
+    (Box::new(make_proof), code_proof)
+}
+
+/// Encode the calldata of `BonsaiStarter.executeOneStep(programId, proof)`.
+pub fn encode_execute_one_step(program_id: [u8; 32], osp_proof_bytes: Vec<u8>) -> Vec<u8> {
+    let mut calldata = short_signature(
+        "executeOneStep",
+        &[ParamType::FixedBytes(32), ParamType::Bytes],
+    )
+    .to_vec();
+    calldata.extend(ethabi::encode(&[
+        Token::FixedBytes(program_id.to_vec()),
+        Token::Bytes(osp_proof_bytes),
+    ]));
+    calldata
+}
+
+/// Encode the calldata of `BonsaiStarter.executeSteps(programId, proofs)`.
+pub fn encode_execute_steps(program_id: [u8; 32], osp_proofs: Vec<Vec<u8>>) -> Vec<u8> {
+    let mut calldata = short_signature(
+        "executeSteps",
+        &[
+            ParamType::FixedBytes(32),
+            ParamType::Array(Box::new(ParamType::Bytes)),
+        ],
+    )
+    .to_vec();
+    calldata.extend(ethabi::encode(&[
+        Token::FixedBytes(program_id.to_vec()),
+        Token::Array(osp_proofs.into_iter().map(Token::Bytes).collect()),
+    ]));
+    calldata
+}
+
+/// The osp proof of a step, with the code roots and the state hashes before
+/// and after executing it.
+pub struct OspInput {
+    pub code_proof: CodeProof<OspHasher>,
+    pub proof: Vec<u8>,
+    pub pre_state: [u8; 32],
+    /// zero if the inst traps
+    pub post_state: [u8; 32],
+    /// the trap of the inst, which ends the program at the pre state
+    pub trap: Option<String>,
+}
+
+impl OspInput {
+    pub fn request(&self) -> OspRequest {
+        OspRequest {
+            inst_root: self.code_proof.inst_root,
+            func_root: self.code_proof.func_root,
+            proof: self.proof.clone(),
+        }
+    }
+
+    /// The guest input, see [`OspRequest`].
+    pub fn input(&self) -> Vec<u8> {
+        self.request().encode()
+    }
 
-    let osp_proof = store
-        .osp_proof::<DefaultMemoryConfig<MerkleKeccak256>>(&code_merkle, instance)
-        .make_osp_proof_v0(pc)?;
+    /// The journal expected from the guest.
+    pub fn journal(&self) -> OspJournal {
+        let CodeProof {
+            inst_root,
+            func_root,
+            ..
+        } = self.code_proof;
+        match self.trap {
+            Some(_) => OspJournal::trapped(inst_root, func_root, self.pre_state),
+            None => OspJournal::ok(inst_root, func_root, self.pre_state, self.post_state),
+        }
+    }
 
-    log::info!("osp inst: {:?}", osp_proof.inst_proof.inst);
+    /// The id of the program in `BonsaiStarter`, see [`program_id`].
+    pub fn program_id(&self) -> [u8; 32] {
+        program_id(self.code_proof.inst_root, self.code_proof.func_root)
+    }
 
-    Ok((osp_proof, code_proof))
+    /// The calldata of `executeOneStep`, see [`encode_execute_one_step`].
+    pub fn calldata(&self) -> Vec<u8> {
+        encode_execute_one_step(self.program_id(), self.proof.clone())
+    }
 }
 
-pub fn create_env<'a>(step: u64) -> Result<ExecutorEnv<'a>> {
-    let (mut osp_proof, code_proof) = gen_osp_proof_for_test(step)?;
+pub fn gen_osp_input(program: &Program<impl HostImports<()>>, step: u64) -> Result<OspInput> {
+    let input = OspGenerator::new(program)?.gen_osp_input(step)?;
+    let code_proof = &input.code_proof;
 
     // for fib :
     // 0xccc2d8707343c7348538f6d0114fab4e20437ec900592ba0d126fab4e19648fe
@@ -97,18 +566,12 @@ pub fn create_env<'a>(step: u64) -> Result<ExecutorEnv<'a>> {
     log::info!("code func_root: 0x{}", hex::encode(code_proof.func_root));
     log::info!("code inst_root: 0x{}", hex::encode(code_proof.inst_root));
 
-    let osp_proof_bytes = osp_proof.encode();
-
-    log::debug!("env: osp_proof len {}", osp_proof_bytes.len(),);
-
-    let pre_proof_hash = osp_proof.hash();
-    osp_proof.run(&code_proof)?;
-    let post_proof_hash = osp_proof.hash();
+    log::debug!("env: osp_proof len {}", input.proof.len(),);
 
     log::info!(
         "executor hash: {:?} -> {:?}",
-        H256::from(pre_proof_hash),
-        H256::from(post_proof_hash)
+        H256::from(input.pre_state),
+        H256::from(input.post_state)
     );
 
     log::info!(
@@ -121,52 +584,259 @@ pub fn create_env<'a>(step: u64) -> Result<ExecutorEnv<'a>> {
         "in  0x{} 0x{} 0x{}",
         hex::encode(code_proof.func_root),
         hex::encode(code_proof.inst_root),
-        hex::encode(osp_proof_bytes.clone())
+        hex::encode(&input.proof)
     );
 
-    // abi.encode(instRoot, funcRoot, proof)
-    let data = ethabi::encode(&[
-        Token::FixedBytes(code_proof.inst_root.to_vec()),
-        Token::FixedBytes(code_proof.func_root.to_vec()),
-        Token::Bytes(osp_proof_bytes),
-    ]);
-
-    {
-        // TODO: should use ethabi::decode_whole, but have some errors
-        use ethabi::ParamType;
-        let input = ethabi::decode(
-            &[
-                ParamType::FixedBytes(32),
-                ParamType::FixedBytes(32),
-                ParamType::Bytes,
-            ],
-            &data,
-        )
-        .unwrap();
-
-        let inst_root: [u8; 32] = input[0]
-            .clone()
-            .into_fixed_bytes()
-            .unwrap()
-            .try_into()
-            .unwrap();
-        let func_root: [u8; 32] = input[1]
-            .clone()
-            .into_fixed_bytes()
-            .unwrap()
-            .try_into()
-            .unwrap();
-        let osp_proof_bytes = input[2].clone().into_bytes().unwrap();
+    Ok(input)
+}
+
+/// The osp proofs of the consecutive steps for the multi step guest.
+pub struct OspMultiInput {
+    pub inputs: Vec<OspInput>,
+}
+
+impl OspMultiInput {
+    pub fn request(&self) -> OspMultiRequest {
+        let code_proof = &self.inputs[0].code_proof;
+        OspMultiRequest {
+            inst_root: code_proof.inst_root,
+            func_root: code_proof.func_root,
+            proofs: self
+                .inputs
+                .iter()
+                .map(|input| input.proof.clone())
+                .collect(),
+        }
+    }
+
+    /// The guest input, see [`OspMultiRequest`].
+    pub fn input(&self) -> Vec<u8> {
+        self.request().encode()
+    }
 
-        log::info!(
-            "res 0x{} 0x{} 0x{}",
-            hex::encode(func_root),
-            hex::encode(inst_root),
-            hex::encode(osp_proof_bytes)
+    /// The journal expected from the guest, from the pre state of the first
+    /// step to the post state of the last step, or trapped if the last step
+    /// traps, with the number of the steps.
+    pub fn journal(&self) -> OspMultiJournal {
+        let (first, last) = (&self.inputs[0], &self.inputs[self.inputs.len() - 1]);
+        let CodeProof {
+            inst_root,
+            func_root,
+            ..
+        } = first.code_proof;
+        let journal = match last.trap {
+            Some(_) => OspJournal::trapped(inst_root, func_root, first.pre_state),
+            None => OspJournal::ok(inst_root, func_root, first.pre_state, last.post_state),
+        };
+        OspMultiJournal {
+            journal,
+            steps: self.inputs.len() as u64,
+        }
+    }
+
+    /// The id of the program in `BonsaiStarter`, see [`program_id`].
+    pub fn program_id(&self) -> [u8; 32] {
+        self.inputs[0].program_id()
+    }
+
+    /// The calldata of `executeSteps`, see [`encode_execute_steps`].
+    pub fn calldata(&self) -> Vec<u8> {
+        let proofs = self
+            .inputs
+            .iter()
+            .map(|input| input.proof.clone())
+            .collect();
+        encode_execute_steps(self.program_id(), proofs)
+    }
+}
+
+/// Generate the osp proofs of the `n` consecutive steps from `step`.
+pub fn gen_osp_multi_input(
+    program: &Program<impl HostImports<()>>,
+    step: u64,
+    n: u64,
+) -> Result<OspMultiInput> {
+    ensure!(n > 0, "should execute at least one step");
+
+    let generator = OspGenerator::new(program)?;
+    let mut execution = generator.execution()?;
+    let inputs = (step..step + n)
+        .map(|step| execution.osp_input(step))
+        .collect::<Result<Vec<_>>>()?;
+    for step in step..step + n - 1 {
+        ensure!(
+            !execution.returns_from_start(step)?,
+            "steps cross from the start function to the call after step {}, which can not \
+             be proven",
+            step
         );
     }
 
-    let env = ExecutorEnv::builder().add_input(&data).build()?;
+    let input = OspMultiInput { inputs };
+    let journal = input.journal().journal;
+    log::info!(
+        "executor hash after {} steps: {:?} -> {:?}",
+        n,
+        H256::from(journal.pre_state),
+        H256::from(journal.post_state)
+    );
+
+    Ok(input)
+}
+
+/// The guest binaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Guest {
+    /// execute one step, see [`OspRequest`]
+    OneStep,
+    /// execute many consecutive steps, see [`OspMultiRequest`]
+    MultiStep,
+}
+
+impl Guest {
+    pub fn elf(&self) -> &'static [u8] {
+        match self {
+            Self::OneStep => ONE_STEP_ELF,
+            Self::MultiStep => MULTI_STEP_ELF,
+        }
+    }
+
+    pub fn image_id(&self) -> [u32; 8] {
+        match self {
+            Self::OneStep => ONE_STEP_ID,
+            Self::MultiStep => MULTI_STEP_ID,
+        }
+    }
+}
+
+pub fn create_env<'a>(input: &[u8]) -> Result<ExecutorEnv<'a>> {
+    let env = ExecutorEnv::builder().add_input(input).build()?;
 
     Ok(env)
 }
+
+#[cfg(test)]
+mod tests {
+    use bonsai_starter_codec::OspStatus;
+
+    use super::*;
+    use crate::call::CallArg;
+
+    fn program(wat: &str, args: Vec<CallArg>) -> Program {
+        Program {
+            source: ModuleSource::Wasm(parse_str(wat).unwrap()),
+            imports: StubImports::default(),
+            call: Call {
+                func: "main".to_string(),
+                args,
+            },
+            run_start: false,
+        }
+    }
+
+    fn finished_at(generator: &OspGenerator<'_, StubImports>, step: u64) -> Finished {
+        match generator.gen_osp_input(step) {
+            Ok(_) => panic!("step {} should be past the end", step),
+            Err(err) => err.downcast::<Finished>().unwrap(),
+        }
+    }
+
+    #[test]
+    fn count_returning_step() {
+        let program = program(r#"(module (func (export "main")))"#, vec![]);
+        let generator = OspGenerator::new(&program).unwrap();
+
+        let finished = generator.finish().unwrap();
+        assert_eq!(finished.steps, 1);
+        assert!(matches!(finished.ending, Ending::Returned(ref outputs) if outputs.is_empty()));
+        assert_eq!(generator.last_step().unwrap(), 0);
+
+        // the returning inst is the last step to prove.
+        assert!(generator.gen_osp_input(0).unwrap().trap.is_none());
+        assert_eq!(finished_at(&generator, 1).steps, 1);
+    }
+
+    #[test]
+    fn count_trapping_step() {
+        let program = program(r#"(module (func (export "main") unreachable))"#, vec![]);
+        let generator = OspGenerator::new(&program).unwrap();
+
+        let finished = generator.finish().unwrap();
+        assert_eq!(finished.steps, 1);
+        assert!(matches!(finished.ending, Ending::Trapped(_)));
+        assert_eq!(generator.last_step().unwrap(), 0);
+
+        // the trapping inst is the last step to prove.
+        assert!(generator.gen_osp_input(0).unwrap().trap.is_some());
+        assert!(matches!(
+            finished_at(&generator, 1).ending,
+            Ending::Trapped(_)
+        ));
+    }
+
+    #[test]
+    fn count_steps_of_fib() {
+        let program = Program {
+            source: ModuleSource::Builtin,
+            imports: StubImports::default(),
+            call: Call::fib(),
+            run_start: false,
+        };
+        let generator = OspGenerator::new(&program).unwrap();
+
+        let finished = generator.finish().unwrap();
+        assert!(matches!(finished.ending, Ending::Returned(ref outputs) if outputs.len() == 1));
+        let last = generator.last_step().unwrap();
+        assert_eq!(last + 1, finished.steps);
+
+        assert!(generator.gen_osp_input(last).unwrap().trap.is_none());
+        assert_eq!(finished_at(&generator, last + 1).steps, finished.steps);
+    }
+
+    /// Prove the last step of the trapping program, as `--last` does.
+    fn assert_last_step_traps(program: &Program) {
+        let generator = OspGenerator::new(program).unwrap();
+        let input = generator
+            .gen_osp_input(generator.last_step().unwrap())
+            .unwrap();
+
+        assert!(input.trap.is_some());
+        assert_eq!(input.post_state, [0; 32]);
+        let journal = input.journal();
+        assert_eq!(journal.status, OspStatus::Trapped);
+        assert_eq!(journal.pre_state, input.pre_state);
+    }
+
+    #[test]
+    fn trap_on_unreachable() {
+        let program = program(
+            r#"(module (func (export "main") (result i32) i32.const 1 drop unreachable))"#,
+            vec![],
+        );
+        assert_last_step_traps(&program);
+    }
+
+    #[test]
+    fn trap_on_division_by_zero() {
+        let program = program(
+            r#"(module
+                (func (export "main") (param i32) (result i32)
+                    i32.const 1
+                    local.get 0
+                    i32.div_s))"#,
+            vec![CallArg::I32(0)],
+        );
+        assert_last_step_traps(&program);
+
+        // the same program returns without the division by zero.
+        let program = Program {
+            call: Call {
+                func: "main".to_string(),
+                args: vec![CallArg::I32(1)],
+            },
+            ..program
+        };
+        let finished = OspGenerator::new(&program).unwrap().finish().unwrap();
+        assert!(matches!(finished.ending, Ending::Returned(_)));
+    }
+}
@@ -0,0 +1,227 @@
+use std::{fmt, fs, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use bonsai_starter_codec::{OspJournal, OspMultiJournal, OspStatus};
+use clap::ValueEnum;
+use ethabi::ethereum_types::H256;
+use risc0_zkvm::SessionReceipt;
+
+use crate::osp::Guest;
+
+/// The format to serialize the receipt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReceiptFormat {
+    Bincode,
+    Json,
+}
+
+pub fn save_receipt(receipt: &SessionReceipt, path: &Path, format: ReceiptFormat) -> Result<()> {
+    let bytes = match format {
+        ReceiptFormat::Bincode => bincode::serialize(receipt)?,
+        ReceiptFormat::Json => serde_json::to_vec(receipt)?,
+    };
+
+    fs::write(path, bytes).with_context(|| format!("write receipt {}", path.display()))
+}
+
+/// Load the receipt saved by [`save_receipt`], the format is detected by the
+/// content.
+pub fn load_receipt(path: &Path) -> Result<SessionReceipt> {
+    let bytes = fs::read(path).with_context(|| format!("read receipt {}", path.display()))?;
+
+    if bytes.first() == Some(&b'{') {
+        serde_json::from_slice(&bytes).context("Failed to deserialize json SessionReceipt")
+    } else {
+        bincode::deserialize(&bytes).context("Failed to deserialize SessionReceipt")
+    }
+}
+
+/// The journal of the one step or the multi step guest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuestJournal {
+    OneStep(OspJournal),
+    MultiStep(OspMultiJournal),
+}
+
+impl GuestJournal {
+    pub fn journal(&self) -> &OspJournal {
+        match self {
+            Self::OneStep(journal) => journal,
+            Self::MultiStep(journal) => &journal.journal,
+        }
+    }
+
+    /// The number of the steps from the pre state to the post state.
+    pub fn steps(&self) -> u64 {
+        match self {
+            Self::OneStep(_) => 1,
+            Self::MultiStep(journal) => journal.steps,
+        }
+    }
+}
+
+/// Decode the journal committed by the guest.
+pub fn decode_journal(guest: Guest, journal: &[u8]) -> Result<GuestJournal> {
+    match guest {
+        Guest::OneStep => OspJournal::decode(journal).map(GuestJournal::OneStep),
+        Guest::MultiStep => OspMultiJournal::decode(journal).map(GuestJournal::MultiStep),
+    }
+    .context("decode journal failed")
+}
+
+/// Format the journal as `<status> (<inst_root>, <func_root>): <pre_state> ->
+/// <post_state>`, followed by the steps of the multi step guest.
+pub fn fmt_journal(guest_journal: &GuestJournal) -> String {
+    let journal = guest_journal.journal();
+    let text = format!(
+        "{:?} ({:?}, {:?}): {:?} -> {:?}",
+        journal.status,
+        H256::from(journal.inst_root),
+        H256::from(journal.func_root),
+        H256::from(journal.pre_state),
+        H256::from(journal.post_state)
+    );
+    match guest_journal {
+        GuestJournal::OneStep(_) => text,
+        GuestJournal::MultiStep(journal) => format!("{} after {} steps", text, journal.steps),
+    }
+}
+
+/// Verify the receipt against the image id of the guest, and decode its
+/// journal.
+pub fn verify_receipt(receipt: &SessionReceipt, guest: Guest) -> Result<GuestJournal> {
+    receipt
+        .verify(guest.image_id())
+        .map_err(|err| anyhow!("verify receipt failed: {:?}", err))?;
+
+    decode_journal(guest, &receipt.journal)
+}
+
+/// The fields of the host executed osp proof which differ from the guest
+/// journal, as `(host, guest)`.
+#[derive(Debug)]
+pub struct JournalMismatch {
+    pub status: Option<(OspStatus, OspStatus)>,
+    pub inst_root: Option<(H256, H256)>,
+    pub func_root: Option<(H256, H256)>,
+    pub pre_state: Option<(H256, H256)>,
+    pub post_state: Option<(H256, H256)>,
+    pub steps: Option<(u64, u64)>,
+}
+
+impl fmt::Display for JournalMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "the guest journal differs from the host execution:")?;
+        if let Some((host, guest)) = self.status {
+            writeln!(f, "  status: host {:?}, guest {:?}", host, guest)?;
+        }
+        for (name, diff) in [
+            ("inst_root", self.inst_root),
+            ("func_root", self.func_root),
+            ("pre_state", self.pre_state),
+            ("post_state", self.post_state),
+        ] {
+            if let Some((host, guest)) = diff {
+                writeln!(f, "  {}: host {:?}, guest {:?}", name, host, guest)?;
+            }
+        }
+        if let Some((host, guest)) = self.steps {
+            writeln!(f, "  steps: host {}, guest {}", host, guest)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for JournalMismatch {}
+
+/// Check the guest journal against the one `expected` from the host executed
+/// osp proofs, to detect the drift between the host and guest wasmi.
+pub fn check_journal(
+    expected: &GuestJournal,
+    journal: &GuestJournal,
+) -> Result<(), JournalMismatch> {
+    let steps =
+        (expected.steps() != journal.steps()).then_some((expected.steps(), journal.steps()));
+    let (expected, journal) = (expected.journal(), journal.journal());
+    let diff = |host: [u8; 32], guest: [u8; 32]| {
+        (host != guest).then_some((H256::from(host), H256::from(guest)))
+    };
+
+    let mismatch = JournalMismatch {
+        status: (expected.status != journal.status).then_some((expected.status, journal.status)),
+        inst_root: diff(expected.inst_root, journal.inst_root),
+        func_root: diff(expected.func_root, journal.func_root),
+        pre_state: diff(expected.pre_state, journal.pre_state),
+        post_state: diff(expected.post_state, journal.post_state),
+        steps,
+    };
+
+    if mismatch.status.is_none()
+        && mismatch.inst_root.is_none()
+        && mismatch.func_root.is_none()
+        && mismatch.pre_state.is_none()
+        && mismatch.post_state.is_none()
+        && mismatch.steps.is_none()
+    {
+        Ok(())
+    } else {
+        Err(mismatch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one_step(inst_root: u8, func_root: u8) -> GuestJournal {
+        GuestJournal::OneStep(OspJournal::ok(
+            [inst_root; 32],
+            [func_root; 32],
+            [3; 32],
+            [4; 32],
+        ))
+    }
+
+    fn multi_step(steps: u64) -> GuestJournal {
+        GuestJournal::MultiStep(OspMultiJournal {
+            journal: OspJournal::ok([1; 32], [2; 32], [3; 32], [4; 32]),
+            steps,
+        })
+    }
+
+    #[test]
+    fn check_same_journal() {
+        assert!(check_journal(&one_step(1, 2), &one_step(1, 2)).is_ok());
+        assert!(check_journal(&multi_step(16), &multi_step(16)).is_ok());
+    }
+
+    #[test]
+    fn check_code_roots() {
+        let expected = one_step(1, 2);
+
+        let mismatch = check_journal(&expected, &one_step(5, 2)).unwrap_err();
+        assert_eq!(
+            mismatch.inst_root,
+            Some((H256::from([1; 32]), H256::from([5; 32])))
+        );
+        assert!(mismatch.to_string().contains("inst_root"));
+
+        let mismatch = check_journal(&expected, &one_step(1, 5)).unwrap_err();
+        assert_eq!(
+            mismatch.func_root,
+            Some((H256::from([2; 32]), H256::from([5; 32])))
+        );
+        assert!(mismatch.to_string().contains("func_root"));
+    }
+
+    #[test]
+    fn check_steps() {
+        let mismatch = check_journal(&multi_step(16), &multi_step(15)).unwrap_err();
+        assert_eq!(mismatch.steps, Some((16, 15)));
+        assert!(mismatch.to_string().contains("steps"));
+
+        // the state after the steps is not the post state of one step.
+        let mismatch = check_journal(&one_step(1, 2), &multi_step(16)).unwrap_err();
+        assert_eq!(mismatch.steps, Some((1, 16)));
+    }
+}
@@ -0,0 +1,216 @@
+//! The wasmi step call can not count the steps of the start function run by
+//! the instantiation, so the module is rewritten to export the start function
+//! instead, which is then step called before the entry function.
+
+use anyhow::{anyhow, ensure, Result};
+
+/// The export name of the start function in the rewritten module.
+pub const START_EXPORT: &str = "__osp_start";
+
+const SECTION_EXPORT: u8 = 7;
+const SECTION_START: u8 = 8;
+const EXTERNAL_FUNC: u8 = 0;
+
+fn read_leb_u32(code: &[u8], pos: &mut usize) -> Result<u32> {
+    let mut result = 0u32;
+    let mut shift = 0;
+    loop {
+        let byte = *code
+            .get(*pos)
+            .ok_or_else(|| anyhow!("unexpected end of module"))?;
+        *pos += 1;
+        ensure!(shift < 35, "invalid leb128 u32");
+        result |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+    }
+}
+
+fn write_leb_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_section(out: &mut Vec<u8>, id: u8, payload: &[u8]) {
+    out.push(id);
+    write_leb_u32(out, payload.len() as u32);
+    out.extend_from_slice(payload);
+}
+
+fn start_export_entry(func_index: u32) -> Vec<u8> {
+    let mut entry = Vec::new();
+    write_leb_u32(&mut entry, START_EXPORT.len() as u32);
+    entry.extend_from_slice(START_EXPORT.as_bytes());
+    entry.push(EXTERNAL_FUNC);
+    write_leb_u32(&mut entry, func_index);
+    entry
+}
+
+/// Rewrite the wasm module to export its start function as [`START_EXPORT`]
+/// and drop the start section, returns `None` if the module has no start
+/// function.
+pub fn export_start(code: &[u8]) -> Result<Option<Vec<u8>>> {
+    ensure!(
+        code.len() >= 8 && code.starts_with(b"\0asm"),
+        "invalid wasm module header"
+    );
+
+    // the sections as (id, payload)
+    let mut sections = Vec::new();
+    let mut pos = 8;
+    while pos < code.len() {
+        let id = code[pos];
+        pos += 1;
+        let size = read_leb_u32(code, &mut pos)? as usize;
+        let payload = code
+            .get(pos..pos + size)
+            .ok_or_else(|| anyhow!("section {} out of module bounds", id))?;
+        pos += size;
+        sections.push((id, payload));
+    }
+
+    let start = match sections.iter().find(|(id, _)| *id == SECTION_START) {
+        Some((_, payload)) => read_leb_u32(payload, &mut 0)?,
+        None => return Ok(None),
+    };
+
+    let mut out = code[..8].to_vec();
+    let has_export = sections.iter().any(|(id, _)| *id == SECTION_EXPORT);
+    for (id, payload) in sections {
+        match id {
+            SECTION_EXPORT => {
+                let mut pos = 0;
+                let count = read_leb_u32(payload, &mut pos)?;
+                let exports = &payload[pos..];
+
+                let mut section = Vec::new();
+                write_leb_u32(&mut section, count + 1);
+                section.extend_from_slice(exports);
+                section.extend(start_export_entry(start));
+                write_section(&mut out, SECTION_EXPORT, &section);
+            }
+            // the export section is just before the start section, so put it
+            // at the place of the start section if the module has none.
+            SECTION_START if !has_export => {
+                let mut section = Vec::new();
+                write_leb_u32(&mut section, 1);
+                section.extend(start_export_entry(start));
+                write_section(&mut out, SECTION_EXPORT, &section);
+            }
+            SECTION_START => {}
+            _ => write_section(&mut out, id, payload),
+        }
+    }
+
+    Ok(Some(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmi::{AsContextMut, Engine, Error, Extern, Instance, Linker, Module, StepResult, Store};
+
+    use super::*;
+    use crate::imports::{HostImports, StubImport, StubImports};
+
+    /// Instantiate the rewritten module with the stub `env.f`, the module
+    /// should validate and have no start section left.
+    fn instantiate(code: &[u8]) -> (Store<()>, Instance) {
+        let engine = Engine::default();
+        let module = Module::new(&engine, code).expect("rewritten module should validate");
+        let mut store = Store::new(&engine, ());
+        let mut linker = <Linker<()>>::new();
+        let imports = StubImports {
+            imports: vec![StubImport {
+                module: "env".to_string(),
+                name: "f".to_string(),
+                results: vec![],
+            }],
+        };
+        if module.imports().next().is_some() {
+            imports.define(&mut store, &mut linker, &module).unwrap();
+        }
+
+        let instance = linker
+            .instantiate(store.as_context_mut(), &module)
+            .and_then(|pre| pre.ensure_no_start(store.as_context_mut()))
+            .expect("rewritten module should have no start section");
+        (store, instance)
+    }
+
+    fn call(
+        store: &mut Store<()>,
+        instance: Instance,
+        name: &str,
+    ) -> Result<StepResult<()>, Error> {
+        let func = instance
+            .get_export(store.as_context_mut(), name)
+            .and_then(Extern::into_func)
+            .unwrap_or_else(|| panic!("`{}` should be exported", name));
+        func.step_call(store.as_context_mut(), &[], &mut [], None)
+    }
+
+    #[test]
+    fn exports_start_with_export_section() {
+        let code = wat::parse_str(
+            r#"(module
+                (func $init unreachable)
+                (func (export "main"))
+                (start $init))"#,
+        )
+        .unwrap();
+        let code = export_start(&code).unwrap().expect("module has a start");
+
+        let (mut store, instance) = instantiate(&code);
+        assert!(matches!(
+            call(&mut store, instance, "main"),
+            Ok(StepResult::Results(()))
+        ));
+        // the exported start function is `$init`, which traps
+        assert!(matches!(
+            call(&mut store, instance, START_EXPORT),
+            Err(Error::Trap(_))
+        ));
+    }
+
+    #[test]
+    fn exports_start_without_export_section() {
+        let code = wat::parse_str(
+            r#"(module
+                (import "env" "f" (func))
+                (func $init)
+                (start $init)
+                (func unreachable))"#,
+        )
+        .unwrap();
+        let code = export_start(&code).unwrap().expect("module has a start");
+
+        // the exported start function is `$init` after the imported function
+        let (mut store, instance) = instantiate(&code);
+        assert!(matches!(
+            call(&mut store, instance, START_EXPORT),
+            Ok(StepResult::Results(()))
+        ));
+    }
+
+    #[test]
+    fn keeps_module_without_start() {
+        let code = wat::parse_str(r#"(module (func (export "main")))"#).unwrap();
+        assert!(export_start(&code).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_module() {
+        assert!(export_start(b"not wasm").is_err());
+        // a start section larger than the module
+        assert!(export_start(b"\0asm\x01\0\0\0\x08\x05\0").is_err());
+    }
+}
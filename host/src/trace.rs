@@ -0,0 +1,116 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{imports::HostImports, osp::*};
+
+/// The state of the execution at a step, before executing its inst.
+#[derive(Debug, Clone, Serialize)]
+pub struct TraceStep {
+    pub step: u64,
+    pub pc: u32,
+    pub inst: String,
+    pub state: String,
+}
+
+/// Trace the execution of the program from step `from` every `every` steps,
+/// until the program finishes or after step `to`, and write the states to
+/// `out` as json lines. Returns the number of the traced steps.
+///
+/// One store is stepped forward by `every` steps between the traced states,
+/// so the trace costs the steps executed once.
+pub fn trace(
+    program: &Program<impl HostImports<()>>,
+    from: u64,
+    to: Option<u64>,
+    every: u64,
+    mut out: impl Write,
+) -> Result<u64> {
+    let generator = OspGenerator::new(program)?;
+    let mut execution = generator.execution()?;
+
+    let mut traced = 0;
+    for step in (from..=to.unwrap_or(u64::MAX)).step_by(every as usize) {
+        let (pc, osp_proof) = match execution
+            .step_proof(step)
+            .with_context(|| format!("trace step {}", step))?
+        {
+            Ok(step_proof) => step_proof,
+            Err(finished) => {
+                log::info!("{}", finished);
+                break;
+            }
+        };
+
+        let trace_step = TraceStep {
+            step,
+            pc,
+            inst: format!("{:?}", osp_proof.inst_proof.inst),
+            state: format!("0x{}", hex::encode(osp_proof.hash())),
+        };
+
+        serde_json::to_writer(&mut out, &trace_step)?;
+        writeln!(out)?;
+        traced += 1;
+    }
+
+    out.flush().context("Failed to flush the trace")?;
+    Ok(traced)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{call::Call, imports::StubImports};
+
+    fn fib() -> Program {
+        Program {
+            source: ModuleSource::Builtin,
+            imports: StubImports::default(),
+            call: Call::fib(),
+            run_start: false,
+        }
+    }
+
+    /// Trace fib, and check the traced steps and their states against the
+    /// pre states of the osp inputs.
+    fn assert_trace(from: u64, to: Option<u64>, every: u64, expected: Vec<u64>) {
+        let program = fib();
+        let mut out = Vec::new();
+        let traced = trace(&program, from, to, every, &mut out).unwrap();
+        assert_eq!(traced, expected.len() as u64);
+
+        let generator = OspGenerator::new(&program).unwrap();
+        let lines = String::from_utf8(out).unwrap();
+        let lines = lines
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), expected.len());
+        for (line, &step) in lines.iter().zip(&expected) {
+            let pre_state = generator.gen_osp_input(step).unwrap().pre_state;
+            assert_eq!(line["step"], step);
+            assert_eq!(line["state"], format!("0x{}", hex::encode(pre_state)));
+        }
+    }
+
+    #[test]
+    fn trace_every_step_of_fib() {
+        let total = OspGenerator::new(&fib()).unwrap().finish().unwrap().steps;
+
+        // the trace stops at the last step when the program finishes.
+        assert_trace(0, None, 1, (0..total).collect());
+        assert_trace(total - 3, None, 2, vec![total - 3, total - 1]);
+        assert_trace(total, None, 1, vec![]);
+    }
+
+    #[test]
+    fn trace_every_k_steps_of_fib() {
+        assert_trace(5, Some(40), 7, vec![5, 12, 19, 26, 33, 40]);
+        assert_trace(5, Some(39), 7, vec![5, 12, 19, 26, 33]);
+        assert_trace(3, Some(3), 4, vec![3]);
+    }
+}
@@ -0,0 +1,34 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The merkle hashers of the osp proofs shared by the host and the guest.
+//!
+//! Keccak-256 roots can be checked by the EVM, but Keccak runs in software in
+//! the zkVM, while SHA-256 runs on its accelerator and is much cheaper to
+//! prove.
+
+use risc0_zkvm::sha::{Impl, Sha256};
+pub use wasmi::merkle::{MerkleHasher, MerkleKeccak256};
+
+/// The merkle hasher by SHA-256, accelerated in the zkVM.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MerkleSha256;
+
+impl MerkleHasher for MerkleSha256 {
+    fn hash(data: &[u8]) -> [u8; 32] {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(Impl::hash_bytes(data).as_bytes());
+        hash
+    }
+}
@@ -0,0 +1,26 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![no_main]
+
+//! Execute consecutive steps by the SHA-256 osp proofs.
+
+use bonsai_starter_merkle::MerkleSha256;
+use bonsai_starter_methods_guest::{commit_multi_journal, execute_steps};
+
+risc0_zkvm::guest::entry!(main);
+
+fn main() {
+    commit_multi_journal(execute_steps::<MerkleSha256>);
+}
@@ -0,0 +1,161 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::Read;
+
+use bonsai_starter_codec::{OspJournal, OspMultiJournal, OspMultiRequest, OspRequest, OspStatus};
+use bonsai_starter_merkle::MerkleHasher;
+use codec::Decode;
+use risc0_zkvm::guest::env;
+use wasmi::{
+    merkle::DefaultMemoryConfig,
+    proof::{CodeProof, OspProof},
+};
+
+/// Read the input sent from the application contract, and commit the journal
+/// that will be received by the application contract.
+pub fn commit_journal(execute: impl FnOnce(&[u8]) -> OspJournal) {
+    commit(|input_bytes| execute(input_bytes).encode())
+}
+
+/// Read the input of `executeSteps`, and commit the journal of the steps, see
+/// [`commit_journal`].
+pub fn commit_multi_journal(execute: impl FnOnce(&[u8]) -> OspMultiJournal) {
+    commit(|input_bytes| execute(input_bytes).encode())
+}
+
+fn commit(execute: impl FnOnce(&[u8]) -> Vec<u8>) {
+    let mut input_bytes = Vec::<u8>::new();
+    env::stdin().read_to_end(&mut input_bytes).unwrap();
+
+    // Encoded types should match the args expected by the application callback.
+    env::commit_slice(&execute(&input_bytes));
+}
+
+/// Run the SCALE encoded osp proof, returns the state hashes before and after
+/// it, or the status and the pre state hash (zero if not decoded) on failure.
+pub fn run_osp_proof<H: MerkleHasher>(
+    code_proof: &CodeProof<H>,
+    proof: &[u8],
+) -> Result<([u8; 32], [u8; 32]), (OspStatus, [u8; 32])> {
+    let mut osp_proof: OspProof<DefaultMemoryConfig<H>> =
+        Decode::decode(&mut &*proof).map_err(|_| (OspStatus::DecodeError, [0; 32]))?;
+
+    let pre_root = osp_proof.hash();
+
+    osp_proof
+        .run(code_proof)
+        .map_err(|err| (run_status(&err), pre_root))?;
+
+    // the proof hash can check if the proof is correct.
+    let proof_hash = osp_proof.hash();
+
+    Ok((pre_root, proof_hash))
+}
+
+/// A trap of the inst ends the program at the pre state, unlike the other
+/// errors which mean the proof is invalid.
+fn run_status(err: &wasmi::Error) -> OspStatus {
+    match err {
+        wasmi::Error::Trap(_) => OspStatus::Trapped,
+        _ => OspStatus::RunError,
+    }
+}
+
+/// Execute the osp proof of one step, see [`OspRequest`].
+pub fn execute_one_step<H: MerkleHasher>(input_bytes: &[u8]) -> OspJournal {
+    // abi.encode(instRoot, funcRoot, proof)
+    let request = match OspRequest::decode(input_bytes) {
+        Ok(request) => request,
+        Err(_) => return OspJournal::undecodable(),
+    };
+
+    let code_proof = CodeProof::<H> {
+        func_root: request.func_root,
+        inst_root: request.inst_root,
+    };
+
+    match run_osp_proof(&code_proof, &request.proof) {
+        Ok((pre_root, proof_hash)) => {
+            OspJournal::ok(request.inst_root, request.func_root, pre_root, proof_hash)
+        }
+        Err((status, pre_root)) => {
+            OspJournal::failed(status, request.inst_root, request.func_root, pre_root)
+        }
+    }
+}
+
+/// Execute the osp proofs of consecutive steps, see [`OspMultiRequest`].
+///
+/// The journal is as of the one step guest, from the pre state of the first
+/// step to the post state of the last step, or trapped if the last step
+/// traps, with the number of the steps.
+pub fn execute_steps<H: MerkleHasher>(input_bytes: &[u8]) -> OspMultiJournal {
+    // abi.encode(instRoot, funcRoot, proofs)
+    let request = match OspMultiRequest::decode(input_bytes) {
+        Ok(request) if !request.proofs.is_empty() => request,
+        _ => {
+            return OspMultiJournal {
+                journal: OspJournal::undecodable(),
+                steps: 0,
+            }
+        }
+    };
+
+    OspMultiJournal {
+        journal: run_steps::<H>(&request),
+        steps: request.proofs.len() as u64,
+    }
+}
+
+fn run_steps<H: MerkleHasher>(request: &OspMultiRequest) -> OspJournal {
+    let (inst_root, func_root) = (request.inst_root, request.func_root);
+    let code_proof = CodeProof::<H> {
+        func_root,
+        inst_root,
+    };
+
+    let mut start = None;
+    let mut state = [0; 32];
+    for (i, proof) in request.proofs.iter().enumerate() {
+        // only the last step can trap, as the program ends there.
+        let last = i + 1 == request.proofs.len();
+        let (pre_root, proof_hash) = match run_osp_proof(&code_proof, proof) {
+            Ok((pre_root, proof_hash)) => (pre_root, Some(proof_hash)),
+            Err((OspStatus::Trapped, pre_root)) if last => (pre_root, None),
+            Err((OspStatus::Trapped, pre_root)) => {
+                let start = start.unwrap_or(pre_root);
+                return OspJournal::failed(OspStatus::RunError, inst_root, func_root, start);
+            }
+            Err((status, pre_root)) => {
+                return OspJournal::failed(status, inst_root, func_root, start.unwrap_or(pre_root))
+            }
+        };
+
+        match start {
+            None => start = Some(pre_root),
+            Some(start) if pre_root != state => {
+                return OspJournal::failed(OspStatus::RunError, inst_root, func_root, start)
+            }
+            Some(_) => {}
+        }
+
+        match proof_hash {
+            Some(proof_hash) => state = proof_hash,
+            None => return OspJournal::trapped(inst_root, func_root, start.unwrap_or(pre_root)),
+        }
+    }
+
+    OspJournal::ok(inst_root, func_root, start.unwrap_or_default(), state)
+}
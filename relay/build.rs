@@ -19,4 +19,8 @@ fn main() {
         // Tell Cargo that if a source file changes, to rerun this build script.
         project.rerun_if_sources_changed();
     }
+
+    // The `BonsaiStarter` bindings are generated from the abi refreshed by
+    // `scripts/update-abi.sh`.
+    println!("cargo:rerun-if-changed=abi/BonsaiStarter.json");
 }
@@ -12,6 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod signer;
+pub mod starter;
+
 use std::{env, time::Duration};
 
 use anyhow::{anyhow, bail, Context, Result};
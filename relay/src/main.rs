@@ -12,11 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{io::Write, sync::Arc};
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
 
-use anyhow::{Context, Error, Result};
+use anyhow::{bail, Context, Error, Result};
 use bonsai_ethereum_relay::Relayer;
-use bonsai_ethereum_relay_cli::{resolve_guest_entry, resolve_image_output};
+use bonsai_ethereum_relay_cli::{
+    resolve_guest_entry, resolve_image_output,
+    signer::{RelaySigner, SignerKind},
+    starter::{ProgramRoots, StarterClient, StepCallback, StepInput, StepState},
+};
 use bonsai_sdk_alpha::{
     alpha::SdkErr,
     alpha_async::{get_client_from_parts, put_image},
@@ -24,9 +34,8 @@ use bonsai_sdk_alpha::{
 use bonsai_starter_methods::GUEST_LIST;
 use clap::{Parser, Subcommand};
 use ethers::{
-    core::k256::{ecdsa::SigningKey, SecretKey},
     prelude::*,
-    types::Address,
+    types::{Address, H256},
 };
 
 #[derive(Subcommand)]
@@ -61,22 +70,171 @@ pub enum Command {
         /// Bonsai Relay contract address on Ethereum
         #[arg(long, env)]
         relay_address: Address,
+        /// Port of the REST endpoint publishing the proofs off-chain
+        #[arg(long, env, default_value_t = 8080)]
+        publish_port: u16,
+        /// Disable the REST endpoint publishing the proofs off-chain
+        #[arg(long, env)]
+        no_publish: bool,
+        #[command(flatten)]
+        eth: EthArgs,
+    },
+    /// Register the WASM program in the BonsaiStarter contract, prints its id.
+    RegisterProgram {
+        /// BonsaiStarter contract address on Ethereum
+        #[arg(long, env)]
+        starter_address: Address,
+        /// The json emitted by the host with `--emit-format json`, to read
+        /// the code roots from
+        #[arg(long, conflicts_with_all = ["inst_root", "func_root"])]
+        program: Option<PathBuf>,
+        /// The inst root of the program
+        #[arg(long, required_unless_present = "program", requires = "func_root")]
+        inst_root: Option<H256>,
+        /// The func root of the program
+        #[arg(long, required_unless_present = "program", requires = "inst_root")]
+        func_root: Option<H256>,
+        #[command(flatten)]
+        eth: EthArgs,
+    },
+    /// Request the proof of a step from the BonsaiStarter contract.
+    SubmitStep {
+        /// BonsaiStarter contract address on Ethereum
+        #[arg(long, env)]
+        starter_address: Address,
+        /// The json emitted by the host with `--emit-format json`, to read
+        /// the program id and the osp proof from
+        #[arg(long, conflicts_with_all = ["program_id", "proof", "pre_state"])]
+        step: Option<PathBuf>,
+        /// The id of the registered program
+        #[arg(long, required_unless_present = "step", requires = "proof")]
+        program_id: Option<H256>,
+        /// The osp proof as a hex string
+        #[arg(long, required_unless_present = "step", requires = "program_id")]
+        proof: Option<Bytes>,
+        /// The state hash before the step, to wait for
+        #[arg(long)]
+        pre_state: Option<H256>,
+        /// Wait for the proof of the step, prints the post state instead of
+        /// the transaction hash
+        #[arg(long, default_value = "false")]
+        wait: bool,
+        /// The seconds to wait for the proof
+        #[arg(long, default_value_t = 600, requires = "wait")]
+        timeout: u64,
+        #[command(flatten)]
+        eth: EthArgs,
+    },
+    /// Get the post state of a step from the BonsaiStarter contract.
+    GetPostState {
+        /// BonsaiStarter contract address on Ethereum
+        #[arg(long, env)]
+        starter_address: Address,
+        /// The id of the registered program
+        #[arg(long)]
+        program_id: H256,
+        /// The state hash before the step
+        #[arg(long)]
+        pre_state: H256,
         /// Ethereum Node endpoint.
         /// Defaults to Anvil
         #[arg(long, env)]
         eth_node: Option<String>,
-        /// Ethereum chain ID
-        #[arg(long, default_value_t = 31337)]
-        eth_chain_id: u64,
-        /// Wallet Key Identifier. Can be a private key as a hex string, or an
-        /// AWS KMS key identifier.
-        /// Defaults to
-        /// ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80
-        #[arg(short, long, env)]
-        private_key: Option<String>,
     },
+    /// Watch the ExecuteOneStepCallback events of the BonsaiStarter contract,
+    /// prints them as json lines.
+    Watch {
+        /// BonsaiStarter contract address on Ethereum
+        #[arg(long, env)]
+        starter_address: Address,
+        /// Only watch the events of the program
+        #[arg(long)]
+        program_id: Option<H256>,
+        /// Only watch the events of the state hash before the step
+        #[arg(long)]
+        pre_state: Option<H256>,
+        /// Append the json lines to the file instead of stdout
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+        /// Ethereum Node endpoint.
+        /// Defaults to Anvil
+        #[arg(long, env)]
+        eth_node: Option<String>,
+    },
+}
+
+#[derive(clap::Args)]
+pub struct EthArgs {
+    /// Ethereum Node endpoint.
+    /// Defaults to Anvil
+    #[arg(long, env)]
+    eth_node: Option<String>,
+    /// Ethereum chain ID
+    #[arg(long, default_value_t = 31337)]
+    eth_chain_id: u64,
+    /// The kind of the wallet signing the transactions
+    #[arg(long, env, value_enum, default_value_t = SignerKind::PrivateKey)]
+    signer: SignerKind,
+    /// Wallet Key Identifier. Can be a private key as a hex string, or an
+    /// AWS KMS key identifier with `--signer aws-kms`.
+    /// Defaults to
+    /// ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80
+    #[arg(short, long, env)]
+    private_key: Option<String>,
+    /// The encrypted json keystore file with `--signer keystore`
+    #[arg(long, env, required_if_eq("signer", "keystore"))]
+    keystore: Option<PathBuf>,
+    /// The password of the keystore
+    #[arg(
+        long,
+        env,
+        hide_env_values = true,
+        required_if_eq("signer", "keystore")
+    )]
+    keystore_password: Option<String>,
+    /// The AWS KMS endpoint, such as of a local KMS stand-in.
+    /// Defaults to the AWS KMS of the region from the environment
+    #[arg(long, env)]
+    aws_kms_endpoint: Option<String>,
 }
 
+impl EthArgs {
+    async fn signer(&self) -> Result<RelaySigner> {
+        match self.signer {
+            SignerKind::PrivateKey => RelaySigner::from_private_key(
+                self.private_key
+                    .as_deref()
+                    .unwrap_or("ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80"),
+                self.eth_chain_id,
+            ),
+            SignerKind::Keystore => RelaySigner::from_keystore(
+                self.keystore.as_deref().context("keystore not set")?,
+                self.keystore_password
+                    .as_deref()
+                    .context("keystore password not set")?,
+                self.eth_chain_id,
+            ),
+            SignerKind::AwsKms => {
+                RelaySigner::from_aws_kms(
+                    self.private_key
+                        .as_deref()
+                        .context("AWS KMS key identifier not set")?,
+                    self.aws_kms_endpoint.clone(),
+                    self.eth_chain_id,
+                )
+                .await
+            }
+        }
+    }
+
+    async fn client(self) -> Result<Arc<SignerMiddleware<Provider<Ws>, RelaySigner>>> {
+        let signer = self.signer().await?;
+        create_ethers_client(self.eth_node.as_deref(), signer).await
+    }
+}
+
+const DEFAULT_ETH_NODE: &str = "ws://localhost:8545";
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -148,22 +306,15 @@ async fn main() -> Result<(), Error> {
             bonsai_api_url,
             bonsai_api_key,
             relay_address,
-            eth_node,
-            eth_chain_id,
-            private_key,
+            publish_port,
+            no_publish,
+            eth,
         } => {
-            let ethers_client = create_ethers_client_private_key(
-                &eth_node.unwrap_or_else(|| "ws://localhost:8545".to_string()),
-                &private_key.unwrap_or_else(|| {
-                    "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80".to_string()
-                }),
-                eth_chain_id,
-            )
-            .await?;
+            let ethers_client = eth.client().await?;
 
             let relayer = Relayer {
-                publish_mode: true,
-                publish_port: "8080".to_string(),
+                publish_mode: !no_publish,
+                publish_port: publish_port.to_string(),
                 bonsai_api_url: bonsai_api_url
                     .unwrap_or_else(|| "http://localhost:8081".to_string()),
                 bonsai_api_key: bonsai_api_key.unwrap_or_else(|| "".to_string()),
@@ -171,26 +322,140 @@ async fn main() -> Result<(), Error> {
             };
             let _ = tokio::spawn(relayer.run(ethers_client.clone())).await;
         }
+        Command::RegisterProgram {
+            starter_address,
+            program,
+            inst_root,
+            func_root,
+            eth,
+        } => {
+            let program = match (program, inst_root, func_root) {
+                (Some(path), _, _) => ProgramRoots::from_file(&path)?,
+                (None, Some(inst_root), Some(func_root)) => ProgramRoots {
+                    inst_root,
+                    func_root,
+                },
+                _ => unreachable!("enforced by clap"),
+            };
+
+            let ethers_client = eth.client().await?;
+
+            let starter = StarterClient::new(starter_address, ethers_client);
+            let program_id = starter.register_program(&program).await?;
+
+            print!("{program_id:?}");
+            std::io::stdout()
+                .flush()
+                .context("Failed to flush stdout buffer")?;
+        }
+        Command::SubmitStep {
+            starter_address,
+            step,
+            program_id,
+            proof,
+            pre_state,
+            wait,
+            timeout,
+            eth,
+        } => {
+            let step = match (step, program_id, proof) {
+                (Some(path), _, _) => StepInput::from_file(&path)?,
+                (None, Some(program_id), Some(proof)) => StepInput {
+                    program_id,
+                    proof,
+                    pre_state,
+                },
+                _ => unreachable!("enforced by clap"),
+            };
+
+            let ethers_client = eth.client().await?;
+
+            let starter = StarterClient::new(starter_address, ethers_client);
+            if wait {
+                let state = starter
+                    .execute_one_step_and_wait(&step, Duration::from_secs(timeout))
+                    .await?;
+                print!("{:?}", post_state(state)?);
+            } else {
+                let receipt = starter.execute_one_step(&step).await?;
+                print!("{:?}", receipt.transaction_hash);
+            }
+            std::io::stdout()
+                .flush()
+                .context("Failed to flush stdout buffer")?;
+        }
+        Command::GetPostState {
+            starter_address,
+            program_id,
+            pre_state,
+            eth_node,
+        } => {
+            let provider = create_ethers_provider(eth_node.as_deref()).await?;
+
+            let starter = StarterClient::new(starter_address, provider);
+            let state = starter.step_state(program_id, pre_state).await?;
+            print!("{:?}", post_state(state)?);
+            std::io::stdout()
+                .flush()
+                .context("Failed to flush stdout buffer")?;
+        }
+        Command::Watch {
+            starter_address,
+            program_id,
+            pre_state,
+            out,
+            eth_node,
+        } => {
+            let mut out: Box<dyn Write> = match out {
+                Some(path) => Box::new(
+                    OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&path)
+                        .with_context(|| format!("open {}", path.display()))?,
+                ),
+                None => Box::new(io::stdout()),
+            };
+
+            let provider = create_ethers_provider(eth_node.as_deref()).await?;
+
+            let starter = StarterClient::new(starter_address, provider);
+            starter
+                .watch_callbacks(program_id, pre_state, |callback: StepCallback| {
+                    serde_json::to_writer(&mut out, &callback)?;
+                    writeln!(out)?;
+                    out.flush().context("Failed to flush the json lines")?;
+                    Ok(true)
+                })
+                .await?;
+        }
     }
     Ok(())
 }
 
-async fn create_ethers_client_private_key(
-    eth_node: &str,
-    private_key: &str,
-    eth_chain_id: u64,
-) -> Result<Arc<SignerMiddleware<Provider<Ws>, LocalWallet>>> {
-    let web3_provider = Provider::<Ws>::connect(eth_node)
+/// The post state of the accepted step, or the error of the others.
+fn post_state(state: StepState) -> Result<H256> {
+    match state {
+        StepState::Accepted(post_state) => Ok(post_state),
+        StepState::Rejected(status) => bail!("step rejected with status {status}"),
+        StepState::Trapped => bail!("step trapped, the program ends at the pre state"),
+        StepState::Pending => bail!("value not available in cache"),
+    }
+}
+
+async fn create_ethers_provider(eth_node: Option<&str>) -> Result<Arc<Provider<Ws>>> {
+    let web3_provider = Provider::<Ws>::connect(eth_node.unwrap_or(DEFAULT_ETH_NODE))
+        .await
+        .context("unable to connect to websocket")?;
+    Ok(Arc::new(web3_provider))
+}
+
+async fn create_ethers_client(
+    eth_node: Option<&str>,
+    signer: RelaySigner,
+) -> Result<Arc<SignerMiddleware<Provider<Ws>, RelaySigner>>> {
+    let web3_provider = Provider::<Ws>::connect(eth_node.unwrap_or(DEFAULT_ETH_NODE))
         .await
         .context("unable to connect to websocket")?;
-    let web3_wallet_sk_bytes =
-        hex::decode(private_key).context("wallet_key_identifier should be valid hex string")?;
-    let web3_wallet_secret_key =
-        SecretKey::from_slice(&web3_wallet_sk_bytes).context("invalid private key")?;
-    let web3_wallet_signing_key = SigningKey::from(web3_wallet_secret_key);
-    let web3_wallet = LocalWallet::from(web3_wallet_signing_key);
-    Ok(Arc::new(SignerMiddleware::new(
-        web3_provider,
-        web3_wallet.with_chain_id(eth_chain_id),
-    )))
+    Ok(Arc::new(SignerMiddleware::new(web3_provider, signer)))
 }
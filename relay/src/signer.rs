@@ -0,0 +1,237 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fmt, path::Path};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use clap::ValueEnum;
+use ethers::{
+    core::k256::{ecdsa::SigningKey, SecretKey},
+    prelude::*,
+    types::transaction::{eip2718::TypedTransaction, eip712::Eip712},
+};
+use ethers_signers::{AwsSigner, AwsSignerError, WalletError};
+use rusoto_core::Region;
+use rusoto_kms::KmsClient;
+
+/// The kind of the wallet signing the transactions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SignerKind {
+    /// private key as a hex string
+    PrivateKey,
+    /// encrypted json keystore file with its password
+    Keystore,
+    /// AWS KMS key identifier
+    AwsKms,
+}
+
+/// The wallet signing the transactions, so the relay and the clients work
+/// with a plaintext key, a keystore or a key held by AWS KMS.
+#[derive(Debug)]
+pub enum RelaySigner {
+    Local(LocalWallet),
+    Aws(AwsSigner),
+}
+
+impl RelaySigner {
+    /// The wallet of the private key as a hex string.
+    pub fn from_private_key(private_key: &str, chain_id: u64) -> Result<Self> {
+        let sk_bytes = hex::decode(private_key.trim_start_matches("0x"))
+            .context("private key should be valid hex string")?;
+        let secret_key = SecretKey::from_slice(&sk_bytes).context("invalid private key")?;
+        let wallet = LocalWallet::from(SigningKey::from(secret_key));
+        Ok(Self::Local(wallet.with_chain_id(chain_id)))
+    }
+
+    /// The wallet decrypted from the json keystore file.
+    pub fn from_keystore(path: &Path, password: &str, chain_id: u64) -> Result<Self> {
+        let wallet = LocalWallet::decrypt_keystore(path, password)
+            .with_context(|| format!("failed to decrypt keystore {}", path.display()))?;
+        Ok(Self::Local(wallet.with_chain_id(chain_id)))
+    }
+
+    /// The signer of the AWS KMS key, in the region from the environment.
+    /// `endpoint` overrides the KMS endpoint, such as of a local KMS stand-in.
+    pub async fn from_aws_kms(
+        key_id: &str,
+        endpoint: Option<String>,
+        chain_id: u64,
+    ) -> Result<Self> {
+        let region = match endpoint {
+            Some(endpoint) => Region::Custom {
+                name: Region::default().name().to_string(),
+                endpoint,
+            },
+            None => Region::default(),
+        };
+        let signer = AwsSigner::new(KmsClient::new(region), key_id, chain_id)
+            .await
+            .with_context(|| format!("failed to load AWS KMS key {}", key_id))?;
+        Ok(Self::Aws(signer))
+    }
+}
+
+/// The error of [`RelaySigner`].
+#[derive(Debug)]
+pub enum RelaySignerError {
+    Local(WalletError),
+    Aws(AwsSignerError),
+}
+
+impl fmt::Display for RelaySignerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local(err) => write!(f, "local wallet: {}", err),
+            Self::Aws(err) => write!(f, "AWS KMS signer: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for RelaySignerError {}
+
+#[async_trait]
+impl Signer for RelaySigner {
+    type Error = RelaySignerError;
+
+    async fn sign_message<S: Send + Sync + AsRef<[u8]>>(
+        &self,
+        message: S,
+    ) -> Result<Signature, Self::Error> {
+        match self {
+            Self::Local(wallet) => wallet
+                .sign_message(message)
+                .await
+                .map_err(RelaySignerError::Local),
+            Self::Aws(signer) => signer
+                .sign_message(message)
+                .await
+                .map_err(RelaySignerError::Aws),
+        }
+    }
+
+    async fn sign_transaction(&self, message: &TypedTransaction) -> Result<Signature, Self::Error> {
+        match self {
+            Self::Local(wallet) => wallet
+                .sign_transaction(message)
+                .await
+                .map_err(RelaySignerError::Local),
+            Self::Aws(signer) => signer
+                .sign_transaction(message)
+                .await
+                .map_err(RelaySignerError::Aws),
+        }
+    }
+
+    async fn sign_typed_data<T: Eip712 + Send + Sync>(
+        &self,
+        payload: &T,
+    ) -> Result<Signature, Self::Error> {
+        match self {
+            Self::Local(wallet) => wallet
+                .sign_typed_data(payload)
+                .await
+                .map_err(RelaySignerError::Local),
+            Self::Aws(signer) => signer
+                .sign_typed_data(payload)
+                .await
+                .map_err(RelaySignerError::Aws),
+        }
+    }
+
+    fn address(&self) -> Address {
+        match self {
+            Self::Local(wallet) => wallet.address(),
+            Self::Aws(signer) => signer.address(),
+        }
+    }
+
+    fn chain_id(&self) -> u64 {
+        match self {
+            Self::Local(wallet) => wallet.chain_id(),
+            Self::Aws(signer) => signer.chain_id(),
+        }
+    }
+
+    fn with_chain_id<T: Into<u64>>(self, chain_id: T) -> Self {
+        match self {
+            Self::Local(wallet) => Self::Local(wallet.with_chain_id(chain_id)),
+            Self::Aws(signer) => Self::Aws(signer.with_chain_id(chain_id)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use ethers::core::rand::thread_rng;
+
+    use super::*;
+
+    /// The first dev account of anvil.
+    const PRIVATE_KEY: &str = "ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+    const ADDRESS: &str = "f39fd6e51aad88f6f4ce6ab8827279cfffb92266";
+
+    async fn assert_signs(signer: &RelaySigner) {
+        let signature = signer.sign_message("step").await.unwrap();
+        assert_eq!(signature.recover("step").unwrap(), signer.address());
+    }
+
+    #[tokio::test]
+    async fn signer_from_private_key() {
+        let address = ADDRESS.parse::<Address>().unwrap();
+        for private_key in [PRIVATE_KEY.to_string(), format!("0x{}", PRIVATE_KEY)] {
+            let signer = RelaySigner::from_private_key(&private_key, 31337).unwrap();
+            assert_eq!(signer.address(), address);
+            assert_eq!(signer.chain_id(), 31337);
+            assert_signs(&signer).await;
+        }
+
+        assert!(RelaySigner::from_private_key("0xzz", 31337).is_err());
+        assert!(RelaySigner::from_private_key("0x01", 31337).is_err());
+    }
+
+    #[tokio::test]
+    async fn signer_from_keystore() {
+        let dir = env::temp_dir().join(format!("relay-signer-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (wallet, name) =
+            LocalWallet::new_keystore(&dir, &mut thread_rng(), "password", None).unwrap();
+        let path = dir.join(name);
+
+        let signer = RelaySigner::from_keystore(&path, "password", 5).unwrap();
+        assert_eq!(signer.address(), wallet.address());
+        assert_eq!(signer.chain_id(), 5);
+        assert_signs(&signer).await;
+
+        assert!(RelaySigner::from_keystore(&path, "wrong", 5).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Runs against the AWS KMS key of `AWS_KMS_KEY_ID`, of a local KMS
+    /// stand-in at `AWS_KMS_ENDPOINT` if set.
+    #[tokio::test]
+    #[ignore = "requires AWS_KMS_KEY_ID of a secp256k1 KMS key, run with --ignored"]
+    async fn signer_from_aws_kms() {
+        let key_id = env::var("AWS_KMS_KEY_ID").expect("AWS_KMS_KEY_ID not set");
+        let endpoint = env::var("AWS_KMS_ENDPOINT").ok();
+
+        let signer = RelaySigner::from_aws_kms(&key_id, endpoint, 31337)
+            .await
+            .unwrap();
+        assert_eq!(signer.chain_id(), 31337);
+        assert_signs(&signer).await;
+    }
+}
@@ -0,0 +1,347 @@
+// Copyright 2023 RISC Zero, Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fs, path::Path, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, bail, Context, Result};
+use bonsai_starter_codec::program_id;
+use ethers::{abi::Detokenize, prelude::*};
+use serde::{Deserialize, Serialize};
+
+abigen!(
+    BonsaiStarter,
+    "abi/BonsaiStarter.json",
+    event_derives(serde::Deserialize, serde::Serialize)
+);
+
+/// The code roots of a WASM program, as in the json emitted by the host with
+/// `--emit-format json`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ProgramRoots {
+    pub inst_root: H256,
+    pub func_root: H256,
+}
+
+impl ProgramRoots {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        read_json(path)
+    }
+
+    /// The id of the program in `BonsaiStarter`.
+    pub fn id(&self) -> H256 {
+        H256(program_id(self.inst_root.0, self.func_root.0))
+    }
+}
+
+/// The osp proof of a step, as in the json emitted by the host with
+/// `--emit-format json`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StepInput {
+    pub program_id: H256,
+    pub proof: Bytes,
+    /// the state hash before the step, only needed to wait for the step
+    #[serde(default)]
+    pub pre_state: Option<H256>,
+}
+
+impl StepInput {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        read_json(path)
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let json = fs::read(path).with_context(|| format!("read {}", path.display()))?;
+    serde_json::from_slice(&json).with_context(|| format!("parse {}", path.display()))
+}
+
+/// The state of a step in `BonsaiStarter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepState {
+    /// The proof is not yet received.
+    Pending,
+    /// The proof is accepted with the post state.
+    Accepted(H256),
+    /// The proof is rejected with the status.
+    Rejected(u8),
+    /// The inst traps, so the program ends at the pre state.
+    Trapped,
+}
+
+impl StepState {
+    /// Whether the step is proven, so no further proof changes its state. A
+    /// rejection only tells the proof sent is bad, another may be accepted.
+    pub fn is_proven(&self) -> bool {
+        matches!(self, StepState::Accepted(_) | StepState::Trapped)
+    }
+}
+
+/// The accepted step, from the `ExecuteOneStepCallback` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct StepCallback {
+    pub program_id: H256,
+    pub inst_root: H256,
+    pub func_root: H256,
+    pub pre_state: H256,
+    pub post_state: H256,
+    pub block_number: U64,
+    pub transaction_hash: H256,
+}
+
+impl StepCallback {
+    fn new(event: ExecuteOneStepCallbackFilter, meta: LogMeta) -> Self {
+        Self {
+            program_id: H256(event.program_id),
+            inst_root: H256(event.inst_root),
+            func_root: H256(event.func_root),
+            pre_state: H256(event.pre_state),
+            post_state: H256(event.post_state),
+            block_number: meta.block_number,
+            transaction_hash: meta.transaction_hash,
+        }
+    }
+}
+
+/// The client of `BonsaiStarter`.
+pub struct StarterClient<M> {
+    contract: BonsaiStarter<M>,
+}
+
+impl<M: Middleware + 'static> StarterClient<M> {
+    pub fn new(address: Address, client: Arc<M>) -> Self {
+        Self {
+            contract: BonsaiStarter::new(address, client),
+        }
+    }
+
+    pub fn contract(&self) -> &BonsaiStarter<M> {
+        &self.contract
+    }
+
+    pub async fn is_registered(&self, program_id: H256) -> Result<bool> {
+        self.contract
+            .is_registered(program_id.0)
+            .call()
+            .await
+            .context("failed to call isRegistered")
+    }
+
+    /// Register the program, returns its id. Registering a registered
+    /// program is a no-op, so no transaction is sent then.
+    pub async fn register_program(&self, program: &ProgramRoots) -> Result<H256> {
+        let program_id = program.id();
+        if self.is_registered(program_id).await? {
+            return Ok(program_id);
+        }
+
+        let call = self
+            .contract
+            .register_program(program.inst_root.0, program.func_root.0);
+        send(call, "registerProgram").await?;
+
+        Ok(program_id)
+    }
+
+    /// Request the proof of the step from Bonsai, returns the receipt of the
+    /// transaction.
+    pub async fn execute_one_step(&self, step: &StepInput) -> Result<TransactionReceipt> {
+        if !self.is_registered(step.program_id).await? {
+            bail!("program {:?} not registered", step.program_id);
+        }
+
+        let call = self
+            .contract
+            .execute_one_step(step.program_id.0, step.proof.clone());
+        send(call, "executeOneStep").await
+    }
+
+    /// The state of the step from `pre_state` of the program, read from the
+    /// storage so it does not revert when the proof is not yet received.
+    pub async fn step_state(&self, program_id: H256, pre_state: H256) -> Result<StepState> {
+        let post_state = self
+            .contract
+            .state_cache(program_id.0, pre_state.0)
+            .call()
+            .await
+            .context("failed to call stateCache")?;
+        if post_state != [0u8; 32] {
+            return Ok(StepState::Accepted(H256(post_state)));
+        }
+
+        let trapped = self
+            .contract
+            .trapped_states(program_id.0, pre_state.0)
+            .call()
+            .await
+            .context("failed to call trappedStates")?;
+        if trapped {
+            return Ok(StepState::Trapped);
+        }
+
+        let status = self
+            .contract
+            .rejected_states(program_id.0, pre_state.0)
+            .call()
+            .await
+            .context("failed to call rejectedStates")?;
+        if status != 0 {
+            return Ok(StepState::Rejected(status));
+        }
+
+        Ok(StepState::Pending)
+    }
+}
+
+impl<M: Middleware + 'static> StarterClient<M>
+where
+    M::Provider: PubsubClient,
+{
+    /// Subscribe to the `ExecuteOneStepCallback` events, optionally of the
+    /// program and the pre state, and pass them to `f` until it returns
+    /// `false`.
+    pub async fn watch_callbacks(
+        &self,
+        program_id: Option<H256>,
+        pre_state: Option<H256>,
+        mut f: impl FnMut(StepCallback) -> Result<bool>,
+    ) -> Result<()> {
+        let mut event = self.contract.execute_one_step_callback_filter();
+        if let Some(program_id) = program_id {
+            event = event.topic1(program_id);
+        }
+        if let Some(pre_state) = pre_state {
+            event = event.topic2(pre_state);
+        }
+
+        let mut stream = event
+            .subscribe_with_meta()
+            .await
+            .context("failed to subscribe ExecuteOneStepCallback")?;
+        while let Some(item) = stream.next().await {
+            let (event, meta) = item.context("failed to decode ExecuteOneStepCallback")?;
+            if !f(StepCallback::new(event, meta))? {
+                return Ok(());
+            }
+        }
+
+        bail!("ExecuteOneStepCallback subscription closed")
+    }
+
+    /// Request the proof of the step from Bonsai, and wait for its callback
+    /// until `timeout`, returns the accepted, rejected or trapped state.
+    ///
+    /// A step already accepted or trapped is returned without sending the
+    /// transaction, as no callback would come for it, while a step rejected
+    /// before is sent again. The events are subscribed before sending the
+    /// transaction, so the callback is not missed however fast the proof
+    /// lands.
+    pub async fn execute_one_step_and_wait(
+        &self,
+        step: &StepInput,
+        timeout: Duration,
+    ) -> Result<StepState> {
+        let pre_state = step
+            .pre_state
+            .context("the pre state is needed to wait for the step")?;
+
+        let state = self.step_state(step.program_id, pre_state).await?;
+        if state.is_proven() {
+            return Ok(state);
+        }
+
+        let accepted = self
+            .contract
+            .execute_one_step_callback_filter()
+            .topic1(step.program_id)
+            .topic2(pre_state);
+        let mut accepted = accepted
+            .subscribe()
+            .await
+            .context("failed to subscribe ExecuteOneStepCallback")?;
+        let rejected = self
+            .contract
+            .execute_one_step_rejected_filter()
+            .topic1(step.program_id)
+            .topic2(pre_state);
+        let mut rejected = rejected
+            .subscribe()
+            .await
+            .context("failed to subscribe ExecuteOneStepRejected")?;
+        let trapped = self
+            .contract
+            .execute_one_step_trapped_filter()
+            .topic1(step.program_id)
+            .topic2(pre_state);
+        let mut trapped = trapped
+            .subscribe()
+            .await
+            .context("failed to subscribe ExecuteOneStepTrapped")?;
+
+        self.execute_one_step(step).await?;
+
+        // the step may be proven by another request before the subscriptions.
+        let state = self.step_state(step.program_id, pre_state).await?;
+        if state.is_proven() {
+            return Ok(state);
+        }
+
+        let wait = async {
+            tokio::select! {
+                Some(event) = accepted.next() => {
+                    let event = event.context("failed to decode ExecuteOneStepCallback")?;
+                    Ok::<_, anyhow::Error>(StepState::Accepted(H256(event.post_state)))
+                }
+                Some(event) = rejected.next() => {
+                    let event = event.context("failed to decode ExecuteOneStepRejected")?;
+                    Ok(StepState::Rejected(event.status))
+                }
+                Some(event) = trapped.next() => {
+                    event.context("failed to decode ExecuteOneStepTrapped")?;
+                    Ok(StepState::Trapped)
+                }
+                else => bail!("step event subscriptions closed"),
+            }
+        };
+
+        tokio::time::timeout(timeout, wait).await.map_err(|_| {
+            anyhow!(
+                "timed out after {:?} waiting for the step from {:?}",
+                timeout,
+                pre_state
+            )
+        })?
+    }
+}
+
+/// Send the transaction of `call` and wait for its receipt.
+async fn send<M: Middleware + 'static, D: Detokenize>(
+    call: ContractCall<M, D>,
+    name: &str,
+) -> Result<TransactionReceipt> {
+    let receipt = call
+        .send()
+        .await
+        .with_context(|| format!("failed to send {}", name))?
+        .await
+        .with_context(|| format!("failed to get the receipt of {}", name))?
+        .with_context(|| format!("{} transaction dropped", name))?;
+    if receipt.status != Some(1.into()) {
+        bail!(
+            "{} transaction {:?} reverted",
+            name,
+            receipt.transaction_hash
+        );
+    }
+    Ok(receipt)
+}
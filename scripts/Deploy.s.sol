@@ -47,23 +47,29 @@ contract Starter is Script, BonsaiCheats {
             vm.envString("BONSAI_API_KEY");
         string memory methodName =
             vm.envString("METHOD_NAME");
+        string memory multiMethodName =
+            vm.envOr("MULTI_METHOD_NAME", string("OSPPROOF_MULTI"));
         uint256 relayPrivateKey =
             vm.envOr("RELAY_PRIVATE_KEY", uint256(0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80));
         vm.startBroadcast(relayPrivateKey);
 
         IBonsaiRelay bonsaiRelay = IBonsaiRelay(relayContract);
         bytes32 imageId = uploadImage(methodName, bonsaiApiUrl, bonsaiApiKey);
+        bytes32 multiImageId = uploadImage(multiMethodName, bonsaiApiUrl, bonsaiApiKey);
         
         // Deploy a new starter instance (or replace with deployment of your own contract here)
-        // TODO: code root from run
-        BonsaiStarter starter = new BonsaiStarter(
-            bonsaiRelay, imageId,
+        BonsaiStarter starter = new BonsaiStarter(bonsaiRelay, imageId, multiImageId);
+
+        // Register the built-in fib program of the host
+        bytes32 programId = starter.registerProgram(
             0x7080aa6f23c6857049c90bc7103a883b2fbe2f4ab895834a06a87a18d9a60a87,
             0xccc2d8707343c7348538f6d0114fab4e20437ec900592ba0d126fab4e19648fe
         );
 
         console.logBytes32(imageId);
+        console.logBytes32(multiImageId);
         console.logAddress(address(starter));
+        console.logBytes32(programId);
 
         vm.stopBroadcast();
     }
@@ -0,0 +1,8 @@
+#!/usr/bin/env bash
+# Refresh the abi the relay cli generates the `BonsaiStarter` bindings from,
+# after changing the contract.
+set -euo pipefail
+
+cd "$(dirname "$0")/.."
+forge build
+jq '.abi' out/BonsaiStarter.sol/BonsaiStarter.json > relay/abi/BonsaiStarter.json
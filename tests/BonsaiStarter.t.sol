@@ -21,32 +21,180 @@ import {IBonsaiRelay} from "bonsai-lib-sol/IBonsaiRelay.sol";
 import {BonsaiStarter} from "contracts/BonsaiStarter.sol";
 
 contract BonsaiStarterTest is BonsaiTest {
+    bytes32 constant INST_ROOT = 0x7080aa6f23c6857049c90bc7103a883b2fbe2f4ab895834a06a87a18d9a60a87;
+    bytes32 constant FUNC_ROOT = 0xccc2d8707343c7348538f6d0114fab4e20437ec900592ba0d126fab4e19648fe;
+    bytes32 constant PRE_STATE = 0x9b14a1ab2325170896ce70a985ec7a9d2c54bcfdb601d5b20070bc7070f9f70f;
+    bytes32 constant POST_STATE = 0x6b5bc5b43331c950fb9dd63a6dacbc7d4070ad514296d833d95538986adeceb9;
+
     function setUp() public withRelayMock {}
 
-    function testMockCall() public {
-        // Deploy a new starter instance
-        BonsaiStarter starter = new BonsaiStarter(
+    /// @notice Deploy a new starter instance with the one step and the multi step guests
+    function newStarter() internal returns (BonsaiStarter) {
+        return new BonsaiStarter(
             IBonsaiRelay(MOCK_BONSAI_RELAY),
             queryImageId('OSPPROOF'),
-            0x7080aa6f23c6857049c90bc7103a883b2fbe2f4ab895834a06a87a18d9a60a87,
-            0xccc2d8707343c7348538f6d0114fab4e20437ec900592ba0d126fab4e19648fe);
+            queryImageId('OSPPROOF_MULTI'));
+    }
+
+    /// @notice Invoke the callback as the relay does, with the image id appended
+    function storeResult(BonsaiStarter starter, bytes memory journal) internal returns (bool) {
+        vm.prank(address(MOCK_BONSAI_RELAY));
+        (bool success,) = address(starter).call(
+            abi.encodePacked(BonsaiStarter.storeResult.selector, journal, starter.imageID()));
+        return success;
+    }
+
+    /// @notice Invoke the callback of the consecutive steps as the relay does
+    function storeStepsResult(BonsaiStarter starter, bytes memory journal) internal returns (bool) {
+        vm.prank(address(MOCK_BONSAI_RELAY));
+        (bool success,) = address(starter).call(
+            abi.encodePacked(BonsaiStarter.storeStepsResult.selector, journal, starter.multiImageID()));
+        return success;
+    }
+
+    function testMockCall() public {
+        // Deploy a new starter instance
+        BonsaiStarter starter = newStarter();
+        bytes32 programId = starter.registerProgram(INST_ROOT, FUNC_ROOT);
 
         // Anticipate a callback request to the relay
         vm.expectCall(address(MOCK_BONSAI_RELAY), abi.encodeWithSelector(IBonsaiRelay.requestCallback.selector));
         // Request the callback
-        // TODO: fix mock
-        /*
-        starter.executeOneStep(0x00000);
-
-        // Anticipate a callback invocation on the starter contract
-        vm.expectCall(address(starter), abi.encodeWithSelector(BonsaiStarter.storeResult.selector));
-        // Relay the solution as a callback
-        (bool success,) = relayCallback();
-        require(success, "Callback failed");
-
-        // Validate the Fibonacci solution value
-        uint256 result = starter.fibonacci(128);
-        assertEq(result, uint256(407305795904080553832073954));
-        */
+        starter.executeOneStep(programId, hex"00");
+    }
+
+    function testExecuteSteps() public {
+        BonsaiStarter starter = newStarter();
+        bytes32 programId = starter.registerProgram(INST_ROOT, FUNC_ROOT);
+
+        bytes[] memory proofs = new bytes[](2);
+        proofs[0] = hex"00";
+        proofs[1] = hex"01";
+        // Anticipate a callback request of the multi step guest to the relay
+        vm.expectCall(
+            address(MOCK_BONSAI_RELAY),
+            abi.encodeWithSelector(
+                IBonsaiRelay.requestCallback.selector,
+                starter.multiImageID(),
+                abi.encode(INST_ROOT, FUNC_ROOT, proofs),
+                address(starter),
+                BonsaiStarter.storeStepsResult.selector,
+                uint64(100000)
+            )
+        );
+        starter.executeSteps(programId, proofs);
+
+        vm.expectRevert("no step to execute");
+        starter.executeSteps(programId, new bytes[](0));
+    }
+
+    function testStoreStepsResult() public {
+        BonsaiStarter starter = newStarter();
+        bytes32 programId = starter.registerProgram(INST_ROOT, FUNC_ROOT);
+
+        bytes memory journal =
+            abi.encode(starter.STATUS_OK(), INST_ROOT, FUNC_ROOT, PRE_STATE, POST_STATE, uint64(16));
+        require(storeStepsResult(starter, journal), "Callback failed");
+        assertEq(starter.getStepsPostState(programId, PRE_STATE, 16), POST_STATE);
+
+        // The state after the steps is not the one step post state, nor of the other number of steps
+        vm.expectRevert("value not available in cache");
+        starter.getPostState(programId, PRE_STATE);
+        vm.expectRevert("value not available in cache");
+        starter.getStepsPostState(programId, PRE_STATE, 1);
+
+        // A later rejection of the same steps does not override the accepted post state
+        bytes memory rejected =
+            abi.encode(starter.STATUS_RUN_ERROR(), INST_ROOT, FUNC_ROOT, PRE_STATE, bytes32(0), uint64(16));
+        require(storeStepsResult(starter, rejected), "Callback failed");
+        assertEq(starter.getStepsPostState(programId, PRE_STATE, 16), POST_STATE);
+        assertEq(starter.stepsStatus(programId, PRE_STATE, 16), starter.STATUS_OK());
+
+        // The callback of the steps only accepts the journal of the multi step guest
+        vm.prank(address(MOCK_BONSAI_RELAY));
+        (bool success,) = address(starter).call(
+            abi.encodePacked(BonsaiStarter.storeStepsResult.selector, journal, starter.imageID()));
+        require(!success, "Callback should fail");
+    }
+
+    function testRegisterProgram() public {
+        BonsaiStarter starter = newStarter();
+
+        bytes32 programId = starter.programIdOf(INST_ROOT, FUNC_ROOT);
+        assertFalse(starter.isRegistered(programId));
+        vm.expectRevert("program not registered");
+        starter.executeOneStep(programId, hex"00");
+
+        assertEq(starter.registerProgram(INST_ROOT, FUNC_ROOT), programId);
+        assertTrue(starter.isRegistered(programId));
+    }
+
+    function testStoreResultByProgram() public {
+        BonsaiStarter starter = newStarter();
+        bytes32 programId = starter.registerProgram(INST_ROOT, FUNC_ROOT);
+        bytes32 otherId = starter.registerProgram(FUNC_ROOT, INST_ROOT);
+
+        bytes memory journal = abi.encode(starter.STATUS_OK(), INST_ROOT, FUNC_ROOT, PRE_STATE, POST_STATE);
+        require(storeResult(starter, journal), "Callback failed");
+
+        assertEq(starter.getPostState(programId, PRE_STATE), POST_STATE);
+        vm.expectRevert("value not available in cache");
+        starter.getPostState(otherId, PRE_STATE);
+    }
+
+    function testRejectedCallback() public {
+        BonsaiStarter starter = newStarter();
+        bytes32 programId = starter.registerProgram(INST_ROOT, FUNC_ROOT);
+
+        bytes memory journal = abi.encode(starter.STATUS_RUN_ERROR(), INST_ROOT, FUNC_ROOT, PRE_STATE, bytes32(0));
+        require(storeResult(starter, journal), "Callback failed");
+
+        assertEq(starter.getRejectedStatus(programId, PRE_STATE), starter.STATUS_RUN_ERROR());
+        vm.expectRevert("value not available in cache");
+        starter.getPostState(programId, PRE_STATE);
+    }
+
+    function testRejectedThenAcceptedCallback() public {
+        BonsaiStarter starter = newStarter();
+        bytes32 programId = starter.registerProgram(INST_ROOT, FUNC_ROOT);
+
+        // A proof with a bad witness of an honest pre state does not block the pre state
+        bytes memory rejected = abi.encode(starter.STATUS_RUN_ERROR(), INST_ROOT, FUNC_ROOT, PRE_STATE, bytes32(0));
+        require(storeResult(starter, rejected), "Callback failed");
+        assertEq(starter.getRejectedStatus(programId, PRE_STATE), starter.STATUS_RUN_ERROR());
+
+        bytes memory accepted = abi.encode(starter.STATUS_OK(), INST_ROOT, FUNC_ROOT, PRE_STATE, POST_STATE);
+        require(storeResult(starter, accepted), "Callback failed");
+        assertEq(starter.getPostState(programId, PRE_STATE), POST_STATE);
+        assertEq(starter.getRejectedStatus(programId, PRE_STATE), starter.STATUS_OK());
+
+        // Nor does a later bad witness override the accepted post state
+        require(storeResult(starter, rejected), "Callback failed");
+        assertEq(starter.getPostState(programId, PRE_STATE), POST_STATE);
+        assertEq(starter.getRejectedStatus(programId, PRE_STATE), starter.STATUS_OK());
+    }
+
+    function testTrappedCallback() public {
+        BonsaiStarter starter = newStarter();
+        bytes32 programId = starter.registerProgram(INST_ROOT, FUNC_ROOT);
+
+        bytes memory journal = abi.encode(starter.STATUS_TRAPPED(), INST_ROOT, FUNC_ROOT, PRE_STATE, bytes32(0));
+        require(storeResult(starter, journal), "Callback failed");
+
+        // The trapped step is neither a post state nor a rejected proof
+        assertTrue(starter.isTrapped(programId, PRE_STATE));
+        assertEq(starter.getRejectedStatus(programId, PRE_STATE), starter.STATUS_OK());
+        vm.expectRevert("value not available in cache");
+        starter.getPostState(programId, PRE_STATE);
+    }
+
+    function testUnexpectedCodeRootsCallback() public {
+        BonsaiStarter starter = newStarter();
+        starter.registerProgram(INST_ROOT, FUNC_ROOT);
+
+        // The state transition of an unregistered program should be rejected
+        bytes memory journal =
+            abi.encode(starter.STATUS_OK(), bytes32(uint256(1)), FUNC_ROOT, PRE_STATE, POST_STATE);
+        require(!storeResult(starter, journal), "Callback should fail");
     }
 }